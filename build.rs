@@ -0,0 +1,7 @@
+fn main() {
+    // the archive module binds to libarchive directly
+    pkg_config::Config::new()
+        .atleast_version("3.2.0")
+        .probe("libarchive")
+        .expect("Unable to find libarchive");
+}
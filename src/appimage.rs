@@ -0,0 +1,171 @@
+use crate::common_directories;
+use anyhow::{anyhow, Context, Result};
+use std::fs::{copy, create_dir_all, read_to_string, remove_file, write, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub fn is_appimage(path: &Path) -> Result<bool> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("appimage"))
+    {
+        return Ok(true);
+    }
+
+    // AppImages are ELF executables carrying the magic bytes `AI\x01` or `AI\x02` in the padding of the ELF header
+    let mut header = [0u8; 11];
+    let mut file = File::open(path)?;
+
+    if file.read_exact(&mut header).is_err() {
+        return Ok(false);
+    }
+
+    Ok(&header[0..4] == b"\x7fELF" && &header[8..10] == b"AI" && matches!(header[10], 1 | 2))
+}
+
+pub fn get_desktop_entry_path(repository_author: &str, repository_name: &str) -> Result<PathBuf> {
+    let mut desktop_entry_path = common_directories::get_shared_data_path()?;
+    desktop_entry_path.push("applications");
+    desktop_entry_path.push(format!(
+        "dyst-{}-{}.desktop",
+        repository_author, repository_name
+    ));
+
+    Ok(desktop_entry_path)
+}
+
+fn get_icons_path() -> Result<PathBuf> {
+    let mut icons_path = common_directories::get_shared_data_path()?;
+    icons_path.push("icons");
+
+    Ok(icons_path)
+}
+
+pub fn has_desktop_entry(repository_author: &str, repository_name: &str) -> Result<bool> {
+    Ok(get_desktop_entry_path(repository_author, repository_name)?.is_file())
+}
+
+pub fn install_desktop_entry(
+    appimage_path: &Path,
+    repository_author: &str,
+    repository_name: &str,
+) -> Result<()> {
+    let extraction_directory = tempfile::tempdir()?;
+    extract_appimage_files(appimage_path, extraction_directory.path(), "*.desktop")?;
+
+    let mut squashfs_root = extraction_directory.path().to_path_buf();
+    squashfs_root.push("squashfs-root");
+
+    let embedded_desktop_entry = squashfs_root
+        .read_dir()
+        .context("The AppImage does not contain a desktop entry")?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "desktop")
+        })
+        .context("The AppImage does not contain a desktop entry")?;
+    let desktop_entry = read_to_string(embedded_desktop_entry)?;
+
+    let icon_name = desktop_entry
+        .lines()
+        .find_map(|line| line.strip_prefix("Icon="))
+        .map(str::to_string);
+    let mut installed_icon_path = None;
+
+    if let Some(icon_name) = icon_name {
+        for icon_extension in ["svg", "png"] {
+            let icon_file_name = format!("{}.{}", icon_name, icon_extension);
+            extract_appimage_files(appimage_path, extraction_directory.path(), &icon_file_name)?;
+
+            let mut icon_path = squashfs_root.clone();
+            icon_path.push(&icon_file_name);
+
+            if icon_path.is_file() {
+                let mut destination_path = get_icons_path()?;
+                create_dir_all(&destination_path)?;
+                destination_path.push(format!(
+                    "dyst-{}-{}.{}",
+                    repository_author, repository_name, icon_extension
+                ));
+
+                copy(icon_path, &destination_path)?;
+                installed_icon_path = Some(destination_path);
+                break;
+            }
+        }
+    }
+
+    // point the entry at the AppImage in the package store, as the link name may be renamed later on
+    let desktop_entry = desktop_entry
+        .lines()
+        .filter(|line| !line.starts_with("TryExec="))
+        .map(|line| match line.strip_prefix("Exec=") {
+            Some(command) => {
+                let arguments = command.split_once(' ').map(|split| split.1).unwrap_or("");
+                format!("Exec=\"{}\" {}", appimage_path.display(), arguments)
+                    .trim_end()
+                    .to_string()
+            }
+            None => match (line.starts_with("Icon="), &installed_icon_path) {
+                (true, Some(icon_path)) => format!("Icon={}", icon_path.display()),
+                _ => line.to_string(),
+            },
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let desktop_entry_path = get_desktop_entry_path(repository_author, repository_name)?;
+    if let Some(parent) = desktop_entry_path.parent() {
+        create_dir_all(parent)?;
+    }
+    write(desktop_entry_path, desktop_entry + "\n")?;
+
+    Ok(())
+}
+
+pub fn remove_desktop_entry(repository_author: &str, repository_name: &str) -> Result<()> {
+    let desktop_entry_path = get_desktop_entry_path(repository_author, repository_name)?;
+
+    if desktop_entry_path.is_file() {
+        remove_file(desktop_entry_path)?;
+    }
+
+    for icon_extension in ["svg", "png"] {
+        let mut icon_path = get_icons_path()?;
+        icon_path.push(format!(
+            "dyst-{}-{}.{}",
+            repository_author, repository_name, icon_extension
+        ));
+
+        if icon_path.is_file() {
+            remove_file(icon_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// AppImages bundle a squashfs image which only the AppImage runtime itself knows how to unpack
+fn extract_appimage_files(
+    appimage_path: &Path,
+    output_directory: &Path,
+    pattern: &str,
+) -> Result<()> {
+    let status = Command::new(appimage_path)
+        .arg("--appimage-extract")
+        .arg(pattern)
+        .current_dir(output_directory)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("The AppImage could not be executed")?;
+
+    if !status.success() {
+        return Err(anyhow!("The AppImage's files could not be extracted"));
+    }
+
+    Ok(())
+}
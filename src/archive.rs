@@ -0,0 +1,458 @@
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::ffi::{CStr, CString, OsStr};
+use std::fs::read_link;
+use std::io::Write;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// archive-reader only exposed the names and contents of entries, but a safe extraction requires
+// their type, mode and link target as well; thus, libarchive is used directly
+#[repr(C)]
+struct archive {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+struct archive_entry {
+    _unused: [u8; 0],
+}
+
+const ARCHIVE_EOF: c_int = 1;
+const ARCHIVE_OK: c_int = 0;
+const ARCHIVE_WARN: c_int = -20;
+const ARCHIVE_FORMAT_RAW: c_int = 0x90000;
+
+const AE_IFMT: c_uint = 0o170000;
+const AE_IFREG: c_uint = 0o100000;
+const AE_IFLNK: c_uint = 0o120000;
+const AE_IFDIR: c_uint = 0o040000;
+
+#[link(name = "archive")]
+extern "C" {
+    fn archive_read_new() -> *mut archive;
+    fn archive_read_support_filter_all(handle: *mut archive) -> c_int;
+    fn archive_read_support_format_all(handle: *mut archive) -> c_int;
+    fn archive_read_support_format_raw(handle: *mut archive) -> c_int;
+    fn archive_read_open_filename(
+        handle: *mut archive,
+        file_name: *const c_char,
+        block_size: usize,
+    ) -> c_int;
+    fn archive_read_next_header(handle: *mut archive, entry: *mut *mut archive_entry) -> c_int;
+    fn archive_read_data(handle: *mut archive, buffer: *mut c_void, size: usize) -> isize;
+    fn archive_read_free(handle: *mut archive) -> c_int;
+    fn archive_format(handle: *mut archive) -> c_int;
+    fn archive_error_string(handle: *mut archive) -> *const c_char;
+    fn archive_entry_pathname(entry: *mut archive_entry) -> *const c_char;
+    fn archive_entry_symlink(entry: *mut archive_entry) -> *const c_char;
+    fn archive_entry_hardlink(entry: *mut archive_entry) -> *const c_char;
+    fn archive_entry_filetype(entry: *mut archive_entry) -> c_uint;
+    fn archive_entry_perm(entry: *mut archive_entry) -> c_uint;
+    fn archive_entry_mtime(entry: *mut archive_entry) -> i64;
+    fn archive_entry_mtime_is_set(entry: *mut archive_entry) -> c_int;
+}
+
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink(PathBuf),
+    Hardlink(PathBuf),
+    Other,
+}
+
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub modified: Option<SystemTime>,
+}
+
+pub struct ArchiveReader {
+    handle: *mut archive,
+}
+
+impl ArchiveReader {
+    pub fn open(archive_path: &Path) -> Result<ArchiveReader> {
+        let file_name = CString::new(archive_path.as_os_str().as_bytes())?;
+
+        unsafe {
+            let reader = ArchiveReader {
+                handle: archive_read_new(),
+            };
+
+            reader.check(archive_read_support_filter_all(reader.handle))?;
+            // compressed streams that are not an archive (ex. `tool.gz`) are exposed as a single entry
+            reader.check(archive_read_support_format_raw(reader.handle))?;
+            reader.check(archive_read_support_format_all(reader.handle))?;
+            reader.check(archive_read_open_filename(
+                reader.handle,
+                file_name.as_ptr(),
+                1024 * 1024,
+            ))?;
+
+            Ok(reader)
+        }
+    }
+
+    // only meaningful after the first entry has been read
+    pub fn is_raw(&self) -> bool {
+        unsafe { archive_format(self.handle) == ARCHIVE_FORMAT_RAW }
+    }
+
+    pub fn next_entry(&mut self) -> Result<Option<ArchiveEntry>> {
+        let mut entry = std::ptr::null_mut();
+
+        unsafe {
+            match archive_read_next_header(self.handle, &mut entry) {
+                ARCHIVE_EOF => return Ok(None),
+                result => self.check(result)?,
+            }
+
+            let path = Self::read_path(archive_entry_pathname(entry))
+                .context("The archive contains an entry without a name")?;
+            let kind = match (
+                Self::read_path(archive_entry_hardlink(entry)),
+                archive_entry_filetype(entry) & AE_IFMT,
+            ) {
+                (Some(target), _) => EntryKind::Hardlink(target),
+                (None, AE_IFREG) => EntryKind::File,
+                (None, AE_IFDIR) => EntryKind::Directory,
+                (None, AE_IFLNK) => EntryKind::Symlink(
+                    Self::read_path(archive_entry_symlink(entry))
+                        .context("The archive contains a symlink without a target")?,
+                ),
+                _ => EntryKind::Other,
+            };
+
+            Ok(Some(ArchiveEntry {
+                path,
+                kind,
+                mode: archive_entry_perm(entry),
+                modified: match archive_entry_mtime_is_set(entry) {
+                    0 => None,
+                    _ => Some(
+                        UNIX_EPOCH + Duration::from_secs(archive_entry_mtime(entry).max(0) as u64),
+                    ),
+                },
+            }))
+        }
+    }
+
+    // reads the data of the current entry
+    pub fn read_data(
+        &mut self,
+        output: &mut dyn Write,
+        limits: &mut ExtractionLimits,
+    ) -> Result<()> {
+        let mut buffer = vec![0u8; 64 * 1024];
+
+        loop {
+            let read = unsafe {
+                archive_read_data(
+                    self.handle,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    buffer.len(),
+                )
+            };
+
+            if read < 0 {
+                return Err(self.error());
+            } else if read == 0 {
+                break;
+            }
+
+            limits.count_bytes(read as u64)?;
+            output.write_all(&buffer[..read as usize])?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn read_path(path: *const c_char) -> Option<PathBuf> {
+        if path.is_null() {
+            return None;
+        }
+
+        Some(PathBuf::from(OsStr::from_bytes(
+            CStr::from_ptr(path).to_bytes(),
+        )))
+    }
+
+    fn check(&self, result: c_int) -> Result<()> {
+        match result {
+            ARCHIVE_OK | ARCHIVE_WARN => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn error(&self) -> anyhow::Error {
+        let message = unsafe { archive_error_string(self.handle) };
+
+        if message.is_null() {
+            return anyhow!("The archive could not be read");
+        }
+
+        anyhow!(
+            "The archive could not be read ({})",
+            unsafe { CStr::from_ptr(message) }.to_string_lossy()
+        )
+    }
+}
+
+impl Drop for ArchiveReader {
+    fn drop(&mut self) {
+        unsafe {
+            archive_read_free(self.handle);
+        }
+    }
+}
+
+// guards against archive bombs
+pub struct ExtractionLimits {
+    max_total_size: u64,
+    max_file_count: u64,
+    total_size: u64,
+    file_count: u64,
+}
+
+impl ExtractionLimits {
+    pub fn from_env() -> Result<ExtractionLimits> {
+        Ok(ExtractionLimits {
+            max_total_size: Self::read_env("DYST_MAX_EXTRACTED_SIZE", 4 * 1024 * 1024 * 1024)?,
+            max_file_count: Self::read_env("DYST_MAX_EXTRACTED_FILES", 100_000)?,
+            total_size: 0,
+            file_count: 0,
+        })
+    }
+
+    fn read_env(name: &str, default: u64) -> Result<u64> {
+        match env::var(name) {
+            Ok(value) => value
+                .parse()
+                .context(format!("The environment variable {} is not a number", name)),
+            Err(_) => Ok(default),
+        }
+    }
+
+    pub fn count_file(&mut self) -> Result<()> {
+        self.file_count += 1;
+
+        if self.file_count > self.max_file_count {
+            return Err(anyhow!(
+                "The archive contains more than {} files (see DYST_MAX_EXTRACTED_FILES)",
+                self.max_file_count
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn count_bytes(&mut self, size: u64) -> Result<()> {
+        self.total_size += size;
+
+        if self.total_size > self.max_total_size {
+            return Err(anyhow!(
+                "The archive extracts to more than {} bytes (see DYST_MAX_EXTRACTED_SIZE)",
+                self.max_total_size
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+// turns an entry name into a relative path that cannot escape the output directory
+pub fn sanitize_entry_path(entry_path: &Path) -> Result<PathBuf> {
+    let mut sanitized_path = PathBuf::new();
+
+    for component in entry_path.components() {
+        match component {
+            Component::Normal(component) => sanitized_path.push(component),
+            // absolute paths are extracted relative to the output directory, like tar does
+            Component::RootDir | Component::Prefix(_) | Component::CurDir => (),
+            Component::ParentDir => {
+                return Err(anyhow!(
+                    "The archive contains a path traversing outside of the package directory: {}",
+                    entry_path.display()
+                ))
+            }
+        }
+    }
+
+    Ok(sanitized_path)
+}
+
+// whether an entry at `relative_path` would be placed through a symlink below the output directory
+pub fn has_symlinked_parent(output_directory: &Path, relative_path: &Path) -> bool {
+    let mut path = output_directory.to_path_buf();
+
+    for component in relative_path.parent().unwrap_or(Path::new("")).components() {
+        path.push(component);
+
+        if path.is_symlink() {
+            return true;
+        }
+    }
+
+    false
+}
+
+// whether a symlink placed at `link_path` (relative to the output directory) resolves within it
+pub fn is_contained_symlink(link_path: &Path, target: &Path) -> bool {
+    if target.is_absolute() {
+        return false;
+    }
+
+    let mut depth: usize = link_path.components().count().saturating_sub(1);
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+            Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+// whether the symlink at `link_path` really resolves within the output directory, which
+// `is_contained_symlink` can't tell once its target passes through other symlinks of the archive
+// (ex. `y -> .` followed by `x -> y/..`)
+pub fn resolves_within(output_directory: &Path, link_path: &Path) -> bool {
+    let output_directory = match output_directory.canonicalize() {
+        Ok(output_directory) => output_directory,
+        Err(_) => return false,
+    };
+
+    match link_path.canonicalize() {
+        Ok(resolved_path) => resolved_path.starts_with(output_directory),
+        // a dangling link can't be followed, so none of the links it passes through may be involved
+        Err(_) => !passes_through_symlink(link_path),
+    }
+}
+
+fn passes_through_symlink(link_path: &Path) -> bool {
+    let (Some(parent), Ok(target)) = (link_path.parent(), read_link(link_path)) else {
+        return false;
+    };
+    let mut path = parent.to_path_buf();
+
+    for component in target.components() {
+        match component {
+            Component::Normal(component) => path.push(component),
+            Component::ParentDir => {
+                path.pop();
+            }
+            _ => (),
+        }
+
+        if path.is_symlink() {
+            return true;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::create_dir;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn sanitize_entry_path_strips_roots_and_current_directories() {
+        assert_eq!(
+            sanitize_entry_path(Path::new("/usr/bin/tool")).unwrap(),
+            PathBuf::from("usr/bin/tool")
+        );
+        assert_eq!(
+            sanitize_entry_path(Path::new("./bin/./tool")).unwrap(),
+            PathBuf::from("bin/tool")
+        );
+    }
+
+    #[test]
+    fn sanitize_entry_path_rejects_parent_directories() {
+        assert!(sanitize_entry_path(Path::new("../tool")).is_err());
+        assert!(sanitize_entry_path(Path::new("bin/../../tool")).is_err());
+        assert!(sanitize_entry_path(Path::new("bin/../tool")).is_err());
+    }
+
+    #[test]
+    fn is_contained_symlink_allows_targets_within_the_package() {
+        assert!(is_contained_symlink(
+            Path::new("bin/tool"),
+            Path::new("../lib/tool")
+        ));
+        assert!(is_contained_symlink(
+            Path::new("tool"),
+            Path::new("./tool-1.2.3")
+        ));
+        assert!(is_contained_symlink(Path::new("a"), Path::new(".")));
+    }
+
+    #[test]
+    fn is_contained_symlink_rejects_targets_outside_of_the_package() {
+        assert!(!is_contained_symlink(Path::new("tool"), Path::new("..")));
+        assert!(!is_contained_symlink(
+            Path::new("bin/tool"),
+            Path::new("../../tool")
+        ));
+        assert!(!is_contained_symlink(
+            Path::new("bin/tool"),
+            Path::new("../lib/../../tool")
+        ));
+        assert!(!is_contained_symlink(
+            Path::new("tool"),
+            Path::new("/usr/bin/tool")
+        ));
+    }
+
+    #[test]
+    fn resolves_within_follows_links_created_before() {
+        let output_directory = tempfile::tempdir().unwrap();
+        let output_path = output_directory.path();
+        create_dir(output_path.join("lib")).unwrap();
+        symlink(".", output_path.join("y")).unwrap();
+        symlink("y/..", output_path.join("x")).unwrap();
+        symlink("lib", output_path.join("p1")).unwrap();
+        symlink("p1/..", output_path.join("p2")).unwrap();
+        symlink("p2/..", output_path.join("p3")).unwrap();
+        symlink("y/../missing", output_path.join("dangling")).unwrap();
+        symlink("lib/missing", output_path.join("broken")).unwrap();
+
+        assert!(resolves_within(output_path, &output_path.join("y")));
+        assert!(resolves_within(output_path, &output_path.join("p1")));
+        assert!(resolves_within(output_path, &output_path.join("p2")));
+        assert!(resolves_within(output_path, &output_path.join("broken")));
+        assert!(!resolves_within(output_path, &output_path.join("x")));
+        assert!(!resolves_within(output_path, &output_path.join("p3")));
+        assert!(!resolves_within(output_path, &output_path.join("dangling")));
+    }
+
+    #[test]
+    fn has_symlinked_parent_detects_links_extracted_before() {
+        let output_directory = tempfile::tempdir().unwrap();
+        symlink(".", output_directory.path().join("a")).unwrap();
+        create_dir(output_directory.path().join("bin")).unwrap();
+
+        assert!(has_symlinked_parent(
+            output_directory.path(),
+            Path::new("a/b")
+        ));
+        assert!(!has_symlinked_parent(
+            output_directory.path(),
+            Path::new("bin/tool")
+        ));
+        assert!(!has_symlinked_parent(
+            output_directory.path(),
+            Path::new("a")
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use clap_complete::Shell;
+use std::io::stdout;
+
+// subcommands whose first argument is an installed repository
+const REPOSITORY_SUBCOMMANDS: [&str; 9] = [
+    "remove",
+    "use",
+    "resolve",
+    "lock",
+    "unlock",
+    "rename",
+    "list-execs",
+    "info",
+    "history",
+];
+
+pub fn print_completions(shell: Shell, command: &mut clap::Command) -> Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, command, "dyst", &mut script);
+    let script = String::from_utf8(script)?;
+
+    // clap only knows the static parts of the command line, so installed repositories are
+    // looked up through `dyst list` whenever they are completed
+    let subcommands = REPOSITORY_SUBCOMMANDS.join("|");
+    let script = match shell {
+        Shell::Bash => format!(
+            r#"{}
+_dyst_with_repositories() {{
+    if [[ ${{COMP_CWORD}} -eq 2 ]]; then
+        case "${{COMP_WORDS[1]}}" in
+            {})
+                COMPREPLY=($(compgen -W "$(dyst list 2>/dev/null | cut -d ' ' -f 1)" -- "${{COMP_WORDS[2]}}"))
+                return 0
+                ;;
+        esac
+    fi
+
+    _dyst "$@"
+}}
+
+complete -F _dyst_with_repositories -o nosort -o bashdefault -o default dyst
+"#,
+            script, subcommands
+        ),
+        Shell::Zsh => format!(
+            r#"{}
+_dyst_with_repositories() {{
+    if (( CURRENT == 3 )) && [[ "${{words[2]}}" == ({}) ]]; then
+        local -a repositories
+        repositories=(${{(f)"$(dyst list 2>/dev/null | cut -d ' ' -f 1)"}})
+        _describe -t repositories 'installed repositories' repositories
+        return
+    fi
+
+    _dyst "$@"
+}}
+
+compdef _dyst_with_repositories dyst
+if [ "$funcstack[1]" = "_dyst" ]; then
+    _dyst_with_repositories "$@"
+fi
+"#,
+            script.replace(
+                "if [ \"$funcstack[1]\" = \"_dyst\" ]; then\n    _dyst \"$@\"\nelse\n    compdef _dyst dyst\nfi\n",
+                ""
+            ),
+            subcommands
+        ),
+        Shell::Fish => format!(
+            "{}complete -c dyst -n \"__fish_seen_subcommand_from {}; and test (count (commandline -opc)) -eq 2\" -f -a \"(dyst list 2>/dev/null | string split -f 1 ' ')\"\n",
+            script,
+            REPOSITORY_SUBCOMMANDS.join(" ")
+        ),
+        // other shells only complete the static parts
+        _ => script,
+    };
+
+    print!("{}", script);
+
+    Ok(())
+}
+
+pub fn print_man_page(command: clap::Command) -> Result<()> {
+    clap_mangen::Man::new(command).render(&mut stdout())?;
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use crate::config::{self, Config};
+use crate::output::{self, OutputFormat};
+use anyhow::Result;
+use std::env;
+
+// strings are printed without quotes, so that they can be used in scripts
+fn format_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+pub fn get_setting(key: &str) -> Result<()> {
+    config::check_key(key)?;
+
+    if let Some(entry) = Config::read_entries()?
+        .into_iter()
+        .find(|entry| entry.key == key)
+    {
+        println!("{}", format_value(&entry.value));
+    }
+
+    Ok(())
+}
+
+// `None` removes the setting
+pub fn set_setting(key: &str, value: Option<&str>, project: bool) -> Result<()> {
+    let path = match project {
+        true => match config::find_project_config()? {
+            Some(path) => path,
+            None => env::current_dir()?.join(config::PROJECT_CONFIG_NAME),
+        },
+        false => config::global_config_path()?,
+    };
+
+    config::write_setting(&path, key, value, project)
+}
+
+pub fn list_settings(output: OutputFormat) -> Result<()> {
+    let entries = Config::read_entries()?;
+
+    match output {
+        OutputFormat::Plain => {
+            for entry in entries {
+                println!("{} = {}", entry.key, entry.value);
+            }
+        }
+        OutputFormat::Table => output::print_table(
+            &["KEY", "VALUE", "SOURCE"],
+            &entries
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.key.clone(),
+                        format_value(&entry.value),
+                        entry.source.display().to_string(),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&entries)?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,354 @@
+use crate::executables::LinkSelection;
+use crate::shims::Shim;
+use crate::{appimage, common_directories, history, links, manifest, versions};
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fs::{create_dir_all, read_dir, read_link, remove_dir_all, remove_file};
+use std::path::{Path, PathBuf};
+
+struct Doctor {
+    fix: bool,
+    problems: usize,
+    unresolved: usize,
+}
+
+impl Doctor {
+    // repairs are only applied with `--fix`, problems without one are always left to the user
+    fn report(&mut self, problem: String, repair: Option<&dyn Fn() -> Result<()>>) -> Result<()> {
+        println!("Problem: {}", problem);
+        self.problems += 1;
+
+        match repair {
+            Some(repair) if self.fix => {
+                repair()?;
+                println!("  Fixed.");
+            }
+            _ => self.unresolved += 1,
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn check_installation(index_db: &sqlite3::Connection, fix: bool) -> Result<()> {
+    let package_store = common_directories::get_package_store()?;
+    let executables_path = common_directories::get_executables_path()?;
+    let mut doctor = Doctor {
+        fix,
+        problems: 0,
+        unresolved: 0,
+    };
+
+    let mut repositories = Vec::new();
+    let mut statement = index_db.prepare("SELECT repository FROM packages")?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        repositories.push(statement.read::<String>(0)?);
+    }
+
+    for repository in &repositories {
+        let package_path = package_store.join(repository);
+
+        if !package_path.is_dir() {
+            doctor.report(
+                format!(
+                    "'{}' is in the database, but its directory is missing",
+                    repository
+                ),
+                Some(&|| forget_package(index_db, repository, &package_path)),
+            )?;
+        } else if !manifest::is_recorded(index_db, repository)? {
+            // packages installed before files were recorded
+            doctor.report(
+                format!(
+                    "The files of '{}' are not recorded in the database",
+                    repository
+                ),
+                Some(&|| manifest::record_package_files(index_db, repository, &package_path)),
+            )?;
+        } else {
+            for file_path in manifest::read_package_files(index_db, repository)? {
+                if file_path.symlink_metadata().is_err() {
+                    doctor.report(
+                        format!(
+                            "The file {} of '{}' is missing (reinstall to restore it)",
+                            file_path.display(),
+                            repository
+                        ),
+                        None,
+                    )?;
+                }
+            }
+        }
+    }
+
+    let mut installed_versions = Vec::new();
+    let mut statement = index_db.prepare("SELECT repository, tag FROM versions")?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        installed_versions.push((statement.read::<String>(0)?, statement.read::<String>(1)?));
+    }
+
+    for (repository, tag) in &installed_versions {
+        let version_key = versions::get_version_key(repository, tag);
+        let version_path = versions::get_owner_path(&version_key)?;
+
+        if !version_path.is_dir() {
+            doctor.report(
+                format!(
+                    "'{}' of '{}' is in the database, but its directory is missing",
+                    tag, repository
+                ),
+                Some(&|| forget_version(index_db, repository, tag, &version_path)),
+            )?;
+        }
+    }
+
+    for package_path in find_package_directories(&package_store)? {
+        let repository = package_path
+            .strip_prefix(&package_store)?
+            .to_string_lossy()
+            .to_string();
+
+        if repositories.contains(&repository) {
+            continue;
+        }
+
+        // the package store may be any directory, so only what dyst has put there is deleted
+        if manifest::is_recorded_below(index_db, &package_path)?
+            || history::has_history(index_db, &repository)?
+        {
+            doctor.report(
+                format!(
+                    "'{}' is not in the database (left behind in {})",
+                    repository,
+                    package_path.display()
+                ),
+                Some(&|| Ok(remove_dir_all(&package_path)?)),
+            )?;
+        } else {
+            doctor.report(
+                format!(
+                    "{} in the package store was not installed by dyst (move or remove it yourself)",
+                    package_path.display()
+                ),
+                None,
+            )?;
+        }
+    }
+
+    let mut tracked_links = Vec::new();
+    let mut statement =
+        index_db.prepare("SELECT path, repository, target FROM files WHERE kind != 'file'")?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        tracked_links.push((
+            PathBuf::from(statement.read::<String>(0)?),
+            statement.read::<String>(1)?,
+            PathBuf::from(statement.read::<String>(2)?),
+        ));
+    }
+
+    for (link_path, repository, target) in tracked_links {
+        if link_path.symlink_metadata().is_err() {
+            doctor.report(
+                format!(
+                    "The link {} of '{}' is missing (reinstall to restore it)",
+                    link_path.display(),
+                    repository
+                ),
+                Some(&|| links::forget_link(index_db, &link_path)),
+            )?;
+        } else if !link_path.is_symlink() && !Shim::is_shim(&link_path) {
+            doctor.report(
+                format!(
+                    "The link {} of '{}' has been replaced by a file not managed by dyst",
+                    link_path.display(),
+                    repository
+                ),
+                Some(&|| links::forget_link(index_db, &link_path)),
+            )?;
+        } else if !target.exists() {
+            doctor.report(
+                format!(
+                    "The link {} of '{}' points to the missing file {}",
+                    link_path.display(),
+                    repository,
+                    target.display()
+                ),
+                Some(&|| {
+                    remove_file(&link_path)?;
+                    links::forget_link(index_db, &link_path)
+                }),
+            )?;
+        }
+    }
+
+    if executables_path.is_dir() {
+        for entry in read_dir(&executables_path)? {
+            let link_path = entry?.path();
+            let target = match read_link(&link_path) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+
+            if !target.starts_with(&package_store)
+                || links::find_link_owner(index_db, &link_path)?.is_some()
+            {
+                continue;
+            }
+
+            // the first two components below the package store name the repository
+            let repository = target
+                .strip_prefix(&package_store)?
+                .components()
+                .take(2)
+                .collect::<PathBuf>()
+                .to_string_lossy()
+                .to_string();
+
+            if !link_path.exists() || !repositories.contains(&repository) {
+                doctor.report(
+                    format!(
+                        "The link {} points to {}, which is not installed",
+                        link_path.display(),
+                        target.display()
+                    ),
+                    Some(&|| Ok(remove_file(&link_path)?)),
+                )?;
+            } else {
+                // packages installed before links were tracked
+                doctor.report(
+                    format!(
+                        "The link {} of '{}' is not tracked in the database",
+                        link_path.display(),
+                        repository
+                    ),
+                    Some(&|| {
+                        links::track_link(index_db, &repository, &target, &link_path, "executable")
+                    }),
+                )?;
+            }
+        }
+    } else {
+        doctor.report(
+            format!(
+                "The executables path {} does not exist",
+                executables_path.display()
+            ),
+            Some(&|| Ok(create_dir_all(&executables_path)?)),
+        )?;
+    }
+
+    let path_variable = env::var_os("PATH").unwrap_or_default();
+    if !env::split_paths(&path_variable).any(|path| path == executables_path) {
+        doctor.report(
+            format!(
+                "The executables path {} is not in $PATH (add it in your shell's profile)",
+                executables_path.display()
+            ),
+            None,
+        )?;
+    }
+
+    for directory in [&package_store, &executables_path] {
+        if directory.is_dir() && tempfile::tempfile_in(directory).is_err() {
+            doctor.report(format!("{} is not writable", directory.display()), None)?;
+        }
+    }
+
+    if doctor.problems == 0 {
+        println!("No problems found.");
+    } else if doctor.unresolved > 0 && fix {
+        return Err(anyhow!(
+            "{} of {} problems could not be fixed",
+            doctor.unresolved,
+            doctor.problems
+        ));
+    } else if doctor.unresolved > 0 {
+        return Err(anyhow!(
+            "{} problems were found (consider passing `--fix`)",
+            doctor.problems
+        ));
+    }
+
+    Ok(())
+}
+
+// the package store contains `author/name` directories next to the index database and the
+// `.versions` directory; hidden ones next to packages are releases being staged by an install or
+// packages set aside by an update, which may be the only copy of one
+fn find_package_directories(package_store: &Path) -> Result<Vec<PathBuf>> {
+    let mut package_directories = Vec::new();
+
+    if !package_store.is_dir() {
+        return Ok(package_directories);
+    }
+
+    for author_entry in read_dir(package_store)? {
+        let author_path = author_entry?.path();
+
+        if !author_path.is_dir() || is_hidden(&author_path) {
+            continue;
+        }
+
+        for name_entry in read_dir(&author_path)? {
+            let name_path = name_entry?.path();
+
+            if name_path.is_dir() && !is_hidden(&name_path) {
+                package_directories.push(name_path);
+            }
+        }
+    }
+
+    Ok(package_directories)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .starts_with('.')
+}
+
+fn forget_version(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    tag: &str,
+    version_path: &Path,
+) -> Result<()> {
+    let version_key = versions::get_version_key(repository, tag);
+
+    links::remove_links(index_db, &version_key, version_path)?;
+    manifest::remove_package_files(index_db, &version_key, version_path)?;
+
+    let mut statement =
+        index_db.prepare("DELETE FROM versions WHERE repository = ? AND tag = ?")?;
+    statement.bind(1, repository)?;
+    statement.bind(2, tag)?;
+    while statement.next()? != sqlite3::State::Done {}
+
+    Ok(())
+}
+
+fn forget_package(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    package_path: &Path,
+) -> Result<()> {
+    links::remove_links(index_db, repository, package_path)?;
+    manifest::remove_package_files(index_db, repository, package_path)?;
+    LinkSelection::delete(index_db, repository)?;
+
+    let mut statement = index_db.prepare("DELETE FROM packages WHERE repository = ?")?;
+    statement.bind(1, repository)?;
+    while statement.next()? != sqlite3::State::Done {}
+
+    if let Some((repository_author, repository_name)) = repository.split_once('/') {
+        if appimage::has_desktop_entry(repository_author, repository_name)? {
+            appimage::remove_desktop_entry(repository_author, repository_name)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use crate::output::{self, OutputFormat};
+use anyhow::Result;
+use itertools::Itertools;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct HistoryEntry {
+    timestamp: String,
+    repository: String,
+    action: String,
+    old_tag: Option<String>,
+    new_tag: Option<String>,
+    asset_name: Option<String>,
+    checksum: Option<String>,
+    details: Option<String>,
+    user: Option<String>,
+    command_line: String,
+}
+
+impl HistoryEntry {
+    // `jq-1.6 -> jq-1.7` for updates, whichever tag is known otherwise
+    fn change(&self) -> String {
+        match (&self.old_tag, &self.new_tag, &self.details) {
+            (Some(old_tag), Some(new_tag), _) => format!("{} -> {}", old_tag, new_tag),
+            (Some(tag), None, _) | (None, Some(tag), _) => tag.clone(),
+            (None, None, Some(details)) => details.clone(),
+            (None, None, None) => String::new(),
+        }
+    }
+}
+
+pub fn show_history(
+    index_db: &sqlite3::Connection,
+    repository: Option<&str>,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut statement = index_db.prepare("SELECT timestamp, repository, action, oldTag, newTag, assetName, checksum, details, user, commandLine FROM history WHERE ?1 IS NULL OR repository = ?1 ORDER BY id")?;
+    match repository {
+        Some(repository) => statement.bind(1, repository)?,
+        None => statement.bind(1, &sqlite3::Value::Null)?,
+    };
+
+    let mut entries = Vec::new();
+
+    while let sqlite3::State::Row = statement.next()? {
+        entries.push(HistoryEntry {
+            timestamp: statement.read::<String>(0)?,
+            repository: statement.read::<String>(1)?,
+            action: statement.read::<String>(2)?,
+            old_tag: statement.read::<String>(3).ok(),
+            new_tag: statement.read::<String>(4).ok(),
+            asset_name: statement.read::<String>(5).ok(),
+            checksum: statement.read::<String>(6).ok(),
+            details: statement.read::<String>(7).ok(),
+            user: statement.read::<String>(8).ok(),
+            command_line: statement.read::<String>(9)?,
+        });
+    }
+
+    match output {
+        OutputFormat::Plain => {
+            for entry in entries {
+                let change = entry.change();
+                let summary = [&entry.timestamp, &entry.action, &entry.repository, &change]
+                    .into_iter()
+                    .filter(|part| !part.is_empty())
+                    .join(" ");
+
+                println!(
+                    "{} ({}: {})",
+                    summary,
+                    entry.user.as_deref().unwrap_or("unknown"),
+                    entry.command_line
+                );
+            }
+        }
+        OutputFormat::Table => output::print_table(
+            &[
+                "TIMESTAMP",
+                "ACTION",
+                "REPOSITORY",
+                "CHANGE",
+                "USER",
+                "COMMAND",
+            ],
+            &entries
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.timestamp.clone(),
+                        entry.action.clone(),
+                        entry.repository.clone(),
+                        entry.change(),
+                        entry.user.clone().unwrap_or_default(),
+                        entry.command_line.clone(),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&entries)?,
+    }
+
+    Ok(())
+}
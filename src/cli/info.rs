@@ -0,0 +1,246 @@
+use crate::executables::LinkSelection;
+use crate::output::{self, OutputFormat};
+use crate::{common_directories, versions};
+use anyhow::{anyhow, Result};
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Serialize)]
+pub struct LinkRule {
+    pub executable: String,
+    pub link_name: String,
+}
+
+#[derive(Serialize)]
+pub struct Link {
+    pub path: PathBuf,
+    pub target: PathBuf,
+}
+
+// serialized as is for `--output json`, so fields are only ever added
+#[derive(Serialize)]
+pub struct PackageInfo {
+    pub repository: String,
+    pub tag: String,
+    // unknown for packages installed before it was recorded
+    pub installed_at: Option<String>,
+    pub locked: bool,
+    pub prereleases: bool,
+    pub asset_filter: Option<String>,
+    pub link_rules: Vec<LinkRule>,
+    pub exclusive_links: bool,
+    pub asset_name: Option<String>,
+    pub asset_size: Option<u64>,
+    pub asset_url: Option<String>,
+    // the SHA-256 of the downloaded asset
+    pub asset_checksum: Option<String>,
+    pub package_path: PathBuf,
+    pub disk_usage: u64,
+    pub links: Vec<Link>,
+    // unknown if the releases could not be fetched
+    pub latest_tag: Option<String>,
+    pub update_available: Option<bool>,
+    // installed next to `tag`, see `dyst use`
+    pub versions: Vec<String>,
+}
+
+impl PackageInfo {
+    pub async fn collect(
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<PackageInfo> {
+        let repository = format!("{}/{}", repository_author, repository_name);
+
+        let mut statement = index_db.prepare("SELECT tag, installedAt, lock, preReleases, assetFilter, assetName, assetSize, assetUrl, assetChecksum FROM packages WHERE repository = ?")?;
+        statement.bind(1, repository.as_str())?;
+
+        if statement.next()? != sqlite3::State::Row {
+            return Err(anyhow!("The requested repository is not installed"));
+        }
+
+        let mut package_path = common_directories::get_package_store()?;
+        package_path.push(repository_author);
+        package_path.push(repository_name);
+
+        let link_selection =
+            LinkSelection::from_index_db(index_db, repository_author, repository_name)?;
+        let prereleases = statement.read::<i64>(3)? != 0;
+        let tag = statement.read::<String>(0)?;
+        let latest_tag =
+            Self::fetch_latest_tag(repository_author, repository_name, prereleases).await;
+
+        Ok(PackageInfo {
+            repository: repository.clone(),
+            update_available: latest_tag.as_ref().map(|latest_tag| *latest_tag != tag),
+            tag,
+            installed_at: statement.read::<String>(1).ok(),
+            locked: statement.read::<i64>(2)? != 0,
+            prereleases,
+            asset_filter: statement.read::<String>(4).ok(),
+            link_rules: link_selection
+                .rules
+                .into_iter()
+                .map(|(executable, link_name)| LinkRule {
+                    executable,
+                    link_name,
+                })
+                .collect(),
+            exclusive_links: link_selection.exclusive,
+            asset_name: statement.read::<String>(5).ok(),
+            // NULL is read as 0
+            asset_size: statement
+                .read::<i64>(6)
+                .ok()
+                .filter(|size| *size > 0)
+                .map(|size| size as u64),
+            asset_url: statement.read::<String>(7).ok(),
+            asset_checksum: statement.read::<String>(8).ok(),
+            disk_usage: Self::disk_usage(&package_path),
+            package_path,
+            links: Self::read_links(index_db, &repository)?,
+            latest_tag,
+            versions: versions::list_versions(index_db, &repository)?,
+        })
+    }
+
+    pub fn print(&self) {
+        let unknown = String::from("unknown");
+
+        println!("Repository:     {}", self.repository);
+        println!("Tag:            {}", self.tag);
+        println!(
+            "Latest tag:     {}",
+            match (&self.latest_tag, self.update_available) {
+                (Some(latest_tag), Some(true)) => format!("{} (update available)", latest_tag),
+                (Some(latest_tag), _) => latest_tag.clone(),
+                (None, _) => unknown.clone(),
+            }
+        );
+        println!(
+            "Installed:      {}",
+            self.installed_at.as_ref().unwrap_or(&unknown)
+        );
+        if !self.versions.is_empty() {
+            println!("Other versions: {}", self.versions.join(", "));
+        }
+        println!("Locked:         {}", Self::yes_no(self.locked));
+        println!("Prereleases:    {}", Self::yes_no(self.prereleases));
+        println!(
+            "Asset filter:   {}",
+            self.asset_filter.as_deref().unwrap_or("none")
+        );
+        println!(
+            "Asset:          {}",
+            self.asset_name.as_ref().unwrap_or(&unknown)
+        );
+        println!(
+            "Asset size:     {}",
+            match self.asset_size {
+                Some(size) => HumanBytes(size).to_string(),
+                None => unknown.clone(),
+            }
+        );
+        println!(
+            "Source:         {}",
+            self.asset_url.as_ref().unwrap_or(&unknown)
+        );
+        println!(
+            "SHA-256:        {}",
+            self.asset_checksum.as_ref().unwrap_or(&unknown)
+        );
+        println!("Location:       {}", self.package_path.display());
+        println!("Disk usage:     {}", HumanBytes(self.disk_usage));
+
+        if !self.link_rules.is_empty() {
+            println!(
+                "Link rules:     {}{}",
+                self.link_rules
+                    .iter()
+                    .map(|rule| format!("{} -> {}", rule.executable, rule.link_name))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if self.exclusive_links {
+                    " (others are ignored)"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        println!("Links:");
+        for link in &self.links {
+            println!("  {} -> {}", link.path.display(), link.target.display());
+        }
+    }
+
+    fn yes_no(value: bool) -> &'static str {
+        match value {
+            true => "yes",
+            false => "no",
+        }
+    }
+
+    fn disk_usage(package_path: &Path) -> u64 {
+        WalkDir::new(package_path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    fn read_links(index_db: &sqlite3::Connection, repository: &str) -> Result<Vec<Link>> {
+        let mut links = Vec::new();
+        let mut statement = index_db
+            .prepare("SELECT path, target FROM files WHERE repository = ? AND kind != 'file' ORDER BY kind, path")?;
+        statement.bind(1, repository)?;
+
+        while let sqlite3::State::Row = statement.next()? {
+            links.push(Link {
+                path: PathBuf::from(statement.read::<String>(0)?),
+                target: PathBuf::from(statement.read::<String>(1)?),
+            });
+        }
+
+        Ok(links)
+    }
+
+    // the package details are still worth showing when GitHub cannot be reached
+    pub async fn fetch_latest_tag(
+        repository_author: &str,
+        repository_name: &str,
+        including_prerelease: bool,
+    ) -> Option<String> {
+        octocrab::instance()
+            .repos(repository_author, repository_name)
+            .releases()
+            .list()
+            .send()
+            .await
+            .ok()?
+            .into_iter()
+            .find(|release| !release.prerelease || including_prerelease)
+            .map(|release| release.tag_name)
+    }
+}
+
+pub async fn show_package_info(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    output: OutputFormat,
+) -> Result<()> {
+    let package_info = PackageInfo::collect(index_db, repository_author, repository_name).await?;
+
+    match output {
+        OutputFormat::Json => output::print_json(&package_info)?,
+        // key-value pairs are already aligned
+        OutputFormat::Plain | OutputFormat::Table => package_info.print(),
+    }
+
+    Ok(())
+}
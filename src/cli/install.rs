@@ -1,25 +1,37 @@
-use crate::common_directories;
+use crate::archive::{self, ArchiveReader, EntryKind, ExtractionLimits};
+use crate::common_directories::Transaction;
+use crate::config::{Config, Libc};
+use crate::executables::{ExecutableDetector, LinkSelection};
+use crate::extraction_filter::ExtractionFilter;
+use crate::history::HistoryEvent;
+use crate::links::{self, ConflictPolicy};
+use crate::shims::Shim;
+use crate::{appimage, common_directories, download_cache, manifest, shared_files, versions};
 use anyhow::{anyhow, Context, Result};
-use archive_reader::Archive;
 use file_format::{FileFormat, Kind};
 use futures_util::StreamExt;
+use glob::Pattern;
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use octocrab::models::repos::{Asset, Release};
 use regex::Regex;
+use sha2::{Digest, Sha256};
 use std::cmp::min;
 use std::env::consts;
-use std::fs::{create_dir_all, metadata, remove_dir_all, set_permissions, File};
-use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use symlink::symlink_file;
-use walkdir::WalkDir;
+use std::fs::{
+    copy, create_dir_all, hard_link, metadata, remove_dir_all, remove_file, rename,
+    set_permissions, File, Permissions,
+};
+use std::io::{self, Write};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
 
 struct InstallErrorCleanup<'a> {
     index_db: &'a sqlite3::Connection,
     repository: String,
     directory: PathBuf,
+    // other packages' links replaced by `--force`
+    replaced_links: Vec<(PathBuf, links::SavedLink)>,
     persist: bool,
 }
 
@@ -33,6 +45,7 @@ impl InstallErrorCleanup<'_> {
             index_db: index_db,
             repository: repository,
             directory: directory,
+            replaced_links: Vec::new(),
             persist: false,
         }
     }
@@ -44,20 +57,76 @@ impl InstallErrorCleanup<'_> {
 
 impl Drop for InstallErrorCleanup<'_> {
     fn drop(&mut self) {
+        // the database rows are rolled back together with the installation's transaction
         if !self.persist {
-            let mut statement = self
-                .index_db
-                .prepare("DELETE FROM packages WHERE repository = ?")
-                .unwrap();
-            statement.bind(1, self.repository.as_str()).unwrap();
-
-            loop {
-                if statement.next().unwrap() == sqlite3::State::Done {
-                    break;
-                }
-            }
+            let _ = links::remove_links(self.index_db, &self.repository, &self.directory); // ignore error
 
             let _ = remove_dir_all(&self.directory); // ignore error
+            let _ = links::restore_links(&self.replaced_links); // ignore error
+        }
+    }
+}
+
+// the installed package an update replaces, which is put back if installing the new release fails
+struct ReplacedPackage {
+    package_path: PathBuf,
+    backup_directory: tempfile::TempDir,
+    saved_links: Vec<(PathBuf, links::SavedLink)>,
+    restore: bool,
+}
+
+impl ReplacedPackage {
+    // moves the package out of the way; the database rows are dropped within the caller's
+    // transaction, so they come back when it is rolled back
+    pub fn set_aside(
+        index_db: &sqlite3::Connection,
+        repository: &str,
+        package_path: &Path,
+    ) -> Result<ReplacedPackage> {
+        let parent_path = package_path
+            .parent()
+            .context("The package directory has no parent")?;
+        let saved_links = links::save_links(index_db, repository, package_path)?;
+
+        let backup_directory = tempfile::Builder::new()
+            .prefix(".dyst-replaced-")
+            .tempdir_in(parent_path)?;
+        rename(package_path, backup_directory.path().join("package"))?;
+
+        let replaced_package = ReplacedPackage {
+            package_path: package_path.to_path_buf(),
+            backup_directory,
+            saved_links,
+            restore: true,
+        };
+
+        let mut statement = index_db.prepare("DELETE FROM packages WHERE repository = ?")?;
+        statement.bind(1, repository)?;
+        while statement.next()? != sqlite3::State::Done {}
+
+        LinkSelection::delete(index_db, repository)?;
+        links::remove_links(index_db, repository, package_path)?;
+        manifest::forget_package_files(index_db, repository)?;
+
+        Ok(replaced_package)
+    }
+
+    // the old files are removed together with the backup directory
+    pub fn discard(&mut self) {
+        self.restore = false;
+    }
+}
+
+impl Drop for ReplacedPackage {
+    fn drop(&mut self) {
+        if self.restore {
+            // the new release has been removed by `InstallErrorCleanup` at this point
+            let _ = remove_dir_all(&self.package_path); // ignore error
+            let _ = rename(
+                self.backup_directory.path().join("package"),
+                &self.package_path,
+            ); // ignore error
+            let _ = links::restore_links(&self.saved_links); // ignore error
         }
     }
 }
@@ -71,7 +140,15 @@ pub struct PackageInstallation<'a> {
     lock_updates: bool,
     override_latest_tag: Option<String>,
     asset_regex_filter: Option<Regex>,
-    rename_executable: Option<(String, String)>,
+    link_selection: LinkSelection,
+    conflict_policy: ConflictPolicy,
+    shim: Shim,
+    desktop_entry: bool,
+    extraction_filter: ExtractionFilter,
+    executable_detector: ExecutableDetector,
+    replaced_tag: Option<String>,
+    preferred_libc: Option<Libc>,
+    side_by_side: bool,
 }
 
 impl PackageInstallation<'_> {
@@ -89,7 +166,15 @@ impl PackageInstallation<'_> {
             lock_updates: false,
             override_latest_tag: None,
             asset_regex_filter: None,
-            rename_executable: None,
+            link_selection: LinkSelection::default(),
+            conflict_policy: ConflictPolicy::default(),
+            shim: Shim::default(),
+            desktop_entry: false,
+            extraction_filter: ExtractionFilter::default(),
+            executable_detector: ExecutableDetector::default(),
+            replaced_tag: None,
+            preferred_libc: None,
+            side_by_side: false,
         }
     }
 
@@ -110,7 +195,113 @@ impl PackageInstallation<'_> {
     }
 
     pub fn rename_executable(&mut self, old_name: String, new_name: String) {
-        self.rename_executable = Some((old_name, new_name));
+        self.link_selection.rules.push((old_name, new_name));
+    }
+
+    // once an executable is selected, all others are ignored
+    pub fn select_executable(&mut self, name: String, link_name: String) {
+        self.link_selection.rules.push((name, link_name));
+        self.link_selection.exclusive = true;
+    }
+
+    pub fn conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.conflict_policy = policy;
+    }
+
+    // executables are linked through launcher scripts setting these up
+    pub fn environment_variable(&mut self, key: String, value: String) {
+        self.shim.environment.push((key, value));
+    }
+
+    pub fn argument(&mut self, argument: String) {
+        self.shim.arguments.push(argument);
+    }
+
+    // recorded in the history, the installation is an update of the given tag
+    pub fn replaces(&mut self, tag: String) {
+        self.replaced_tag = Some(tag);
+    }
+
+    // breaks the tie between otherwise equally fitting gnu and musl builds
+    pub fn preferred_libc(&mut self, libc: Libc) {
+        self.preferred_libc = Some(libc);
+    }
+
+    // installs the release next to the installed one, linking its executables as `name@version`
+    pub fn side_by_side(&mut self, install: bool) {
+        self.side_by_side = install;
+    }
+
+    pub fn desktop_entry(&mut self, install: bool) {
+        self.desktop_entry = install;
+    }
+
+    pub fn strip_components(&mut self, count: usize) {
+        self.extraction_filter.strip_components = count;
+    }
+
+    pub fn include_paths(&mut self, patterns: Vec<Pattern>) {
+        self.extraction_filter.include_patterns = patterns;
+    }
+
+    pub fn exclude_paths(&mut self, patterns: Vec<Pattern>) {
+        self.extraction_filter.exclude_patterns = patterns;
+    }
+
+    pub fn executable_paths(&mut self, patterns: Vec<Pattern>) {
+        self.executable_detector.executable_patterns = patterns;
+    }
+
+    pub fn non_executable_paths(&mut self, patterns: Vec<Pattern>) {
+        self.executable_detector.non_executable_patterns = patterns;
+    }
+
+    // carries the settings of the installed package over, for updates and additional versions
+    pub fn restore_settings(&mut self) -> Result<()> {
+        let mut statement = self
+            .index_db
+            .prepare("SELECT assetFilter, preReleases FROM packages WHERE repository = ?")?;
+        statement.bind(
+            1,
+            format!("{}/{}", self.repository_author, self.repository_name).as_str(),
+        )?;
+
+        if statement.next()? != sqlite3::State::Row {
+            return Err(anyhow!("The requested repository is not installed"));
+        }
+
+        if let Ok(asset_filter) = statement.read::<String>(0) {
+            self.asset_regex_filter = Some(
+                Regex::new(&asset_filter)
+                    .context("The asset filter contains illegal regex syntax")?,
+            );
+        }
+
+        self.including_prerelease = statement.read::<i64>(1)? != 0;
+        self.link_selection = LinkSelection::from_index_db(
+            self.index_db,
+            self.repository_author,
+            self.repository_name,
+        )?;
+        self.conflict_policy = ConflictPolicy::from_index_db(
+            self.index_db,
+            self.repository_author,
+            self.repository_name,
+        )?;
+        self.shim =
+            Shim::from_index_db(self.index_db, self.repository_author, self.repository_name)?;
+        self.extraction_filter = ExtractionFilter::from_index_db(
+            self.index_db,
+            self.repository_author,
+            self.repository_name,
+        )?;
+        self.executable_detector = ExecutableDetector::from_index_db(
+            self.index_db,
+            self.repository_author,
+            self.repository_name,
+        )?;
+
+        Ok(())
     }
 
     pub async fn fetch_release(&mut self) -> Result<()> {
@@ -152,98 +343,294 @@ impl PackageInstallation<'_> {
     }
 
     pub async fn install(&self) -> Result<()> {
-        if self.selected_release.is_none() {
-            return Err(anyhow!("No release was selected prior to installation"));
-        }
-
+        let selected_release = self
+            .selected_release
+            .clone()
+            .context("No release was selected prior to installation")?;
         let package_store = common_directories::get_package_store()?;
-        let executables_path = common_directories::get_executables_path()?;
-        let selected_release = self.selected_release.clone().unwrap();
 
         println!(
             "Starting installation for release: {}",
             selected_release.tag_name
         );
 
-        if selected_release.assets.clone().into_iter().count() == 0 {
-            return Err(anyhow!("There are no assets available"));
-        }
-
-        let auto_selected_asset = self.auto_select_asset(&selected_release.assets)
-            .context(format!(
-                "An asset could not be automatically selected, try applying a custom filter to select one: {}",
-                selected_release.assets
-                    .clone()
-                    .into_iter()
-                    .map(|asset| asset.name)
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ))?;
+        let auto_selected_asset = self.select_asset(&selected_release)?;
         println!(
             "  Preparing for asset download: {}",
             auto_selected_asset.name
         );
 
-        let mut asset_path = package_store.clone();
-        asset_path.push(self.repository_author);
-        asset_path.push(self.repository_name);
+        let repository = format!("{}/{}", self.repository_author, self.repository_name);
+        let (owner, asset_path, link_suffix) = match self.side_by_side {
+            true => {
+                if versions::is_tag_installed(
+                    self.index_db,
+                    &repository,
+                    &selected_release.tag_name,
+                )? {
+                    return Err(anyhow!("The requested version is already installed"));
+                }
 
-        create_dir_all(&asset_path)?;
-        let mut errdefer = InstallErrorCleanup::new(
-            self.index_db,
-            format!("{}/{}", self.repository_author, self.repository_name),
-            asset_path.clone(),
-        );
+                (
+                    versions::get_version_key(&repository, &selected_release.tag_name),
+                    versions::get_version_path(
+                        self.repository_author,
+                        self.repository_name,
+                        &selected_release.tag_name,
+                    )?,
+                    versions::get_link_suffix(&selected_release.tag_name),
+                )
+            }
+            false => (
+                repository.clone(),
+                package_store
+                    .join(self.repository_author)
+                    .join(self.repository_name),
+                String::new(),
+            ),
+        };
+
+        // the release is extracted next to its destination first, so that a failed download
+        // leaves an installed package untouched
+        let parent_path = asset_path
+            .parent()
+            .context("The package directory has no parent")?;
+        create_dir_all(parent_path)?;
+        let staging_directory = tempfile::Builder::new()
+            .prefix(".dyst-staging-")
+            .tempdir_in(parent_path)?;
 
         println!("  Downloading asset...");
-        Self::download_and_extract_asset(
+        let checksum = Self::download_and_extract_asset(
             auto_selected_asset.browser_download_url.as_str(),
             &auto_selected_asset.name,
-            &asset_path,
+            staging_directory.path(),
+            &self.extraction_filter,
         )
         .await
         .context("Failed to download the asset")?;
 
+        let transaction = Transaction::begin(self.index_db)?;
+
+        // dropped after `errdefer`, so that the new release is gone before the old one returns
+        let mut replaced_package = match (self.replaced_tag.is_some(), asset_path.exists()) {
+            (true, true) => Some(ReplacedPackage::set_aside(
+                self.index_db,
+                &owner,
+                &asset_path,
+            )?),
+            _ => None,
+        };
+
+        rename(staging_directory.path(), &asset_path)?;
+        let mut errdefer =
+            InstallErrorCleanup::new(self.index_db, owner.clone(), asset_path.clone());
+
+        manifest::record_package_files(self.index_db, &owner, &asset_path)?;
+
         println!("\n  Adding an entry to the database");
-        self.add_index_db_entry()?;
+        if self.side_by_side {
+            self.add_version_entry(auto_selected_asset, &checksum)?;
+        } else {
+            self.add_index_db_entry(auto_selected_asset, &checksum)?;
+            self.link_selection.save(
+                self.index_db,
+                self.repository_author,
+                self.repository_name,
+            )?;
+        }
 
         println!("  Creating symlinks to the executables...");
-        for entry in WalkDir::new(&asset_path)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|entry| entry.file_type().is_file())
+        let mut planned_links = Vec::new();
+        let mut desktop_entry_installed = false;
+
+        for path in self
+            .executable_detector
+            .find_executables(&asset_path, &self.extraction_filter)?
         {
-            let path = entry.path();
-            let format = FileFormat::from_file(path)?;
-
-            if format.kind() == Kind::Executable {
-                let mut permissions = metadata(path)?.permissions();
-                permissions.set_mode(0o755);
-                set_permissions(path, permissions)?;
-
-                let mut binary_path = executables_path.clone();
-                let file_name = entry.file_name().to_str().unwrap();
-
-                if self.rename_executable.is_some() {
-                    if self.rename_executable.as_ref().unwrap().0 == file_name {
-                        binary_path.push(&self.rename_executable.as_ref().unwrap().1);
-                    } else {
-                        binary_path.push(file_name);
-                    }
-                } else {
-                    binary_path.push(file_name);
-                }
+            Self::make_executable(&path)?;
+
+            for link_name in self
+                .link_selection
+                .link_names(&path, self.repository_name)?
+            {
+                planned_links.push((path.clone(), format!("{}{}", link_name, link_suffix)));
+            }
+
+            // there is only one desktop entry per repository, which belongs to the default version
+            if self.desktop_entry && !self.side_by_side && appimage::is_appimage(&path)? {
+                println!("  Installing the AppImage's desktop entry...");
+                appimage::install_desktop_entry(
+                    &path,
+                    self.repository_author,
+                    self.repository_name,
+                )?;
+                desktop_entry_installed = true;
+            }
+        }
 
-                symlink_file(path, binary_path)?;
+        for (target, link_path) in links::resolve_conflicts(
+            self.index_db,
+            &owner,
+            self.repository_author,
+            &asset_path,
+            planned_links,
+            self.conflict_policy,
+            &mut errdefer.replaced_links,
+        )? {
+            links::create_link(self.index_db, &owner, &target, &link_path, &self.shim)?;
+        }
+
+        // man pages and completions are those of the default version
+        let shared_files = match self.side_by_side {
+            true => Vec::new(),
+            false => shared_files::find_shared_files(&asset_path, &self.extraction_filter)?,
+        };
+        if !shared_files.is_empty() {
+            println!("  Linking man pages and shell completions...");
+        }
+
+        for (target, link_path) in shared_files {
+            // unlike executables, these are not worth aborting the installation over
+            if link_path.symlink_metadata().is_ok() {
+                println!(
+                    "  Warning: Skipping '{}', which already exists",
+                    link_path.display()
+                );
+                continue;
             }
+
+            links::create_shared_link(self.index_db, &owner, &target, &link_path)?;
         }
 
+        HistoryEvent {
+            action: match self.replaced_tag {
+                Some(_) => "update",
+                None => "install",
+            },
+            old_tag: self.replaced_tag.as_deref(),
+            new_tag: Some(&selected_release.tag_name),
+            asset_name: Some(&auto_selected_asset.name),
+            checksum: Some(&checksum),
+            details: match self.side_by_side {
+                true => Some("side by side"),
+                false => None,
+            },
+        }
+        .record(self.index_db, &repository)?;
+
+        transaction.commit()?;
         errdefer.persist();
 
+        if let Some(replaced_package) = &mut replaced_package {
+            replaced_package.discard();
+
+            // the replaced release's desktop entry is stale once the new one has none
+            if !desktop_entry_installed
+                && appimage::has_desktop_entry(self.repository_author, self.repository_name)?
+            {
+                println!("  Removing the AppImage's desktop entry...");
+                appimage::remove_desktop_entry(self.repository_author, self.repository_name)?;
+            }
+        }
+
         println!("  Done.");
         Ok(())
     }
 
+    // for `dyst run`, nothing is linked or recorded and the package store is left alone
+    pub async fn extract_to_cache(&self) -> Result<PathBuf> {
+        let selected_release = self
+            .selected_release
+            .clone()
+            .context("No release was selected prior to installation")?;
+        let asset = self.select_asset(&selected_release)?;
+
+        let mut cache_path = common_directories::get_cache_path()?;
+        cache_path.push("run");
+        cache_path.push(self.repository_author);
+        cache_path.push(self.repository_name);
+        cache_path.push(selected_release.tag_name.replace('/', "_"));
+        cache_path.push(&asset.name);
+
+        if cache_path.is_dir() {
+            println!("  Using the cached release: {}", selected_release.tag_name);
+        } else {
+            self.extract_release(&cache_path, asset).await?;
+        }
+
+        // a failing cache is no reason not to run the release
+        let cache_size = Config::load()?.cache_size.unwrap_or(0);
+        if let Err(error) = download_cache::evict_extracted_releases(&cache_path, cache_size) {
+            println!("  Warning: The cache could not be cleaned up ({})", error);
+        }
+
+        Ok(cache_path)
+    }
+
+    async fn extract_release(&self, cache_path: &Path, asset: &Asset) -> Result<()> {
+        println!("  Downloading asset: {}", asset.name);
+
+        // extracted next to its destination, so that an interrupted download is never picked up
+        let parent_path = cache_path
+            .parent()
+            .context("The cached release has no directory")?;
+        create_dir_all(parent_path)?;
+        let temporary_directory = tempfile::tempdir_in(parent_path)?;
+
+        Self::download_and_extract_asset(
+            asset.browser_download_url.as_str(),
+            &asset.name,
+            temporary_directory.path(),
+            &self.extraction_filter,
+        )
+        .await
+        .context("Failed to download the asset")?;
+        println!();
+
+        // another run may have extracted the same release in the meantime
+        if let Err(error) = rename(temporary_directory.path(), cache_path) {
+            if !cache_path.is_dir() {
+                return Err(error.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn find_executables(&self, package_path: &Path) -> Result<Vec<PathBuf>> {
+        self.executable_detector
+            .find_executables(package_path, &self.extraction_filter)
+    }
+
+    // keep the modes shipped within archives and only fix up plain downloads
+    pub fn make_executable(path: &Path) -> Result<()> {
+        let mut permissions = metadata(path)?.permissions();
+
+        if permissions.mode() & 0o111 == 0 {
+            permissions.set_mode(0o755);
+            set_permissions(path, permissions)?;
+        }
+
+        Ok(())
+    }
+
+    fn select_asset<'a>(&'a self, release: &'a Release) -> Result<&'a Asset> {
+        if release.assets.is_empty() {
+            return Err(anyhow!("There are no assets available"));
+        }
+
+        self.auto_select_asset(&release.assets)
+            .context(format!(
+                "An asset could not be automatically selected, try applying a custom filter to select one: {}",
+                release.assets
+                    .iter()
+                    .map(|asset| asset.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))
+    }
+
     fn auto_select_asset<'a>(&'a self, assets: &'a Vec<Asset>) -> Option<&Asset> {
         match assets
             .into_iter()
@@ -261,6 +648,10 @@ impl PackageInstallation<'_> {
                         score += asset.name.to_lowercase().matches("amd64").count() as isize;
                     }
 
+                    if let Some(libc) = self.preferred_libc {
+                        score += asset.name.to_lowercase().matches(libc.as_str()).count() as isize;
+                    }
+
                     score -= asset.name.to_lowercase().matches("md5").count() as isize;
                     score -= asset.name.to_lowercase().matches("sha").count() as isize;
 
@@ -268,7 +659,7 @@ impl PackageInstallation<'_> {
                 }
             })
             .filter(|lookup| lookup.0 > 0)
-            .sorted_by(|a, b| b.0.partial_cmp(&a.0).unwrap())
+            .sorted_by(|a, b| b.0.cmp(&a.0))
             .next()
         {
             Some(lookup) => Some(lookup.1),
@@ -279,59 +670,277 @@ impl PackageInstallation<'_> {
     async fn download_and_extract_asset(
         source_url: &str,
         file_base_name: &str,
-        output_directory: &PathBuf,
-    ) -> Result<()> {
+        output_directory: &Path,
+        extraction_filter: &ExtractionFilter,
+    ) -> Result<String> {
         // I considered implementing a stream decompressor/unarchiver for different (combinations of) formats myself
         // but it would be unnecessary yak shaving. Thus, I am required to temporarily store the file
         // on disk to use one of the existing libraries to unarchive it.
-        let file_path = PathBuf::from(file_base_name);
-        let file_extension = file_path.extension().unwrap_or(std::ffi::OsStr::new(""));
-        let response = reqwest::get(source_url).await?;
-        let total_size = response.content_length().unwrap(); // GitHub as a source returns the content length, so unlikely to fail
-        let stream = response.bytes_stream().boxed();
-
-        let progressbar = ProgressBar::new(total_size);
-        progressbar.set_style(ProgressStyle::default_bar()
-            .template("  [{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?
-            .progress_chars("=>-"));
-
-        const UNARCHIVABLE_EXTENSIONS: &'static [&str] =
-            &["tar", "zip", "gz", "bz2", "xz", "zst", "rar"];
-
-        if UNARCHIVABLE_EXTENSIONS.contains(&file_extension.to_str().unwrap()) {
-            let mut temporary_file = tempfile::NamedTempFile::new()?;
-            Self::copy_stream_to_file(&mut temporary_file, stream, &progressbar, &total_size)
-                .await?;
-
-            let temporary_path = temporary_file.into_temp_path();
-            let mut archive = Archive::open(&temporary_path);
-            let file_names = archive
-                .block_size(1024 * 1024)
-                .list_file_names()?
-                .collect::<archive_reader::error::Result<Vec<_>>>()?;
-
-            for file_name in file_names {
-                let mut output_path = output_directory.clone();
-                output_path.push(&file_name);
-                create_dir_all(output_path.parent().unwrap())?;
-
-                if output_path.to_str().unwrap().chars().last().unwrap() != '/' {
-                    let mut output_file = File::create(output_path)?;
-                    let _ = archive.read_file(&file_name, &mut output_file)?;
+        let config = Config::load()?;
+        let cache_size = config.cache_size.unwrap_or(0);
+
+        let temporary_path = match download_cache::lookup(source_url)?.filter(|_| cache_size > 0) {
+            Some(cached_path) => {
+                println!("  Using the cached download");
+
+                let temporary_path = tempfile::NamedTempFile::new()?.into_temp_path();
+                copy(cached_path, &temporary_path)?;
+
+                temporary_path
+            }
+            None => {
+                let response = config.http_client()?.get(source_url).send().await?;
+                let total_size = response
+                    .content_length()
+                    .context("The size of the asset is unknown")?; // GitHub as a source returns the content length, so unlikely to fail
+                let stream = response.bytes_stream().boxed();
+
+                let progressbar = ProgressBar::new(total_size);
+                progressbar.set_style(ProgressStyle::default_bar()
+                    .template("  [{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?
+                    .progress_chars("=>-"));
+
+                let mut temporary_file = tempfile::NamedTempFile::new()?;
+                Self::copy_stream_to_file(&mut temporary_file, stream, &progressbar, &total_size)
+                    .await?;
+                progressbar.finish();
+
+                let temporary_path = temporary_file.into_temp_path();
+
+                // a failing cache is no reason to fail the installation
+                if cache_size > 0 {
+                    if let Err(error) =
+                        download_cache::store(source_url, &temporary_path, cache_size)
+                    {
+                        println!("\n  Warning: The download could not be cached ({})", error);
+                    }
                 }
+
+                temporary_path
             }
+        };
 
-            temporary_path.close()?;
-        } else {
-            let mut output_file = output_directory.clone();
-            output_file.push(file_base_name);
+        // file extensions are unreliable (`.tgz`, `tool.linux`, no extension at all), so sniff the content instead
+        let format = FileFormat::from_file(&temporary_path)?;
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(&temporary_path)?, &mut hasher)?;
+        let checksum = format!("{:x}", hasher.finalize());
+
+        match format {
+            FileFormat::DebianBinaryPackage => {
+                Self::extract_deb_payload(&temporary_path, output_directory, extraction_filter)?
+            }
+            // libarchive strips the rpm header and reads the cpio payload natively
+            FileFormat::RedHatPackageManager => {
+                Self::extract_archive(&temporary_path, output_directory, extraction_filter)?
+            }
+            _ if format.kind() == Kind::Archive => {
+                Self::extract_archive(&temporary_path, output_directory, extraction_filter)?
+            }
+            _ if format.kind() == Kind::Compression => Self::extract_compressed_file(
+                &temporary_path,
+                &format,
+                file_base_name,
+                output_directory,
+                extraction_filter,
+            )?,
+            _ => {
+                let mut output_file = output_directory.to_path_buf();
+                output_file.push(file_base_name);
+
+                copy(&temporary_path, output_file)?;
+            }
+        }
+
+        temporary_path.close()?;
+
+        Ok(checksum)
+    }
+
+    // abstraction for download_and_extract_asset
+    fn extract_archive(
+        archive_path: &Path,
+        output_directory: &Path,
+        extraction_filter: &ExtractionFilter,
+    ) -> Result<()> {
+        let mut archive = ArchiveReader::open(archive_path)?;
+        let mut limits = ExtractionLimits::from_env()?;
+        // symlinks are created last, so that no entry can be written through one of them
+        let mut symlinks = Vec::new();
+        // extracting files into a directory changes its modification time, so it is restored last
+        let mut directories = Vec::new();
+
+        while let Some(entry) = archive.next_entry()? {
+            let entry_path = archive::sanitize_entry_path(&entry.path)?;
+            let relative_path = match extraction_filter.apply(&entry_path) {
+                Some(relative_path) => relative_path,
+                None => continue,
+            };
 
-            let mut destination_file = File::create(output_file)?;
-            Self::copy_stream_to_file(&mut destination_file, stream, &progressbar, &total_size)
-                .await?;
+            let mut output_path = output_directory.to_path_buf();
+            output_path.push(&relative_path);
+
+            match entry.kind {
+                EntryKind::Directory => {
+                    create_dir_all(&output_path)?;
+                    directories.push((output_path, entry.mode, entry.modified));
+                }
+                EntryKind::File => {
+                    limits.count_file()?;
+                    if let Some(parent) = output_path.parent() {
+                        create_dir_all(parent)?;
+                    }
+
+                    let mut output_file = File::create(&output_path)?;
+                    archive.read_data(&mut output_file, &mut limits)?;
+
+                    if let Some(modified) = entry.modified {
+                        output_file.set_modified(modified)?;
+                    }
+
+                    // never restore setuid, setgid or sticky bits and write access for others
+                    set_permissions(
+                        &output_path,
+                        Permissions::from_mode(entry.mode & 0o755 | 0o600),
+                    )?;
+                }
+                EntryKind::Symlink(target) => {
+                    if !archive::is_contained_symlink(&relative_path, &target) {
+                        println!(
+                            "  Warning: Skipping a symlink pointing outside of the package: {}",
+                            relative_path.display()
+                        );
+                        continue;
+                    }
+
+                    limits.count_file()?;
+                    symlinks.push((relative_path, target));
+                }
+                EntryKind::Hardlink(target) => {
+                    let target_path =
+                        match extraction_filter.apply(&archive::sanitize_entry_path(&target)?) {
+                            Some(target_path) => output_directory.join(target_path),
+                            None => continue,
+                        };
+
+                    if target_path.is_file() {
+                        limits.count_file()?;
+                        if let Some(parent) = output_path.parent() {
+                            create_dir_all(parent)?;
+                        }
+                        hard_link(target_path, output_path)?;
+                    }
+                }
+                // device files and fifos have no place in a package
+                EntryKind::Other => (),
+            }
+        }
+
+        let mut created_links = Vec::new();
+        for (relative_path, target) in symlinks {
+            // each link is only checked on its own, so one created earlier must not redirect it
+            // (ex. `a -> .` followed by `a/b -> ..`, which would end up as `b -> ..`)
+            if archive::has_symlinked_parent(output_directory, &relative_path) {
+                println!(
+                    "  Warning: Skipping a symlink placed through another symlink: {}",
+                    relative_path.display()
+                );
+                continue;
+            }
+
+            let link_path = output_directory.join(&relative_path);
+            if let Some(parent) = link_path.parent() {
+                create_dir_all(parent)?;
+            }
+            symlink(target, &link_path)?;
+            created_links.push(relative_path);
         }
 
-        progressbar.finish();
+        // a target may pass through other links of the archive, so where each one ends up is only
+        // known once all of them exist (ex. `y -> .` followed by `x -> y/..`)
+        while let Some(index) = created_links.iter().position(|relative_path| {
+            !archive::resolves_within(output_directory, &output_directory.join(relative_path))
+        }) {
+            let relative_path = created_links.remove(index);
+            println!(
+                "  Warning: Skipping a symlink resolving outside of the package: {}",
+                relative_path.display()
+            );
+            remove_file(output_directory.join(relative_path))?;
+        }
+
+        for (directory_path, mode, modified) in directories {
+            // the owner keeps full access, so the package can always be updated and removed
+            set_permissions(
+                &directory_path,
+                Permissions::from_mode(mode & 0o755 | 0o700),
+            )?;
+
+            if let Some(modified) = modified {
+                File::open(&directory_path)?.set_modified(modified)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // abstraction for download_and_extract_asset
+    fn extract_deb_payload(
+        archive_path: &Path,
+        output_directory: &Path,
+        extraction_filter: &ExtractionFilter,
+    ) -> Result<()> {
+        // a .deb is an ar archive wrapping `control.tar.*` and the actual files in `data.tar.*`
+        let mut archive = ArchiveReader::open(archive_path)?;
+        let mut limits = ExtractionLimits::from_env()?;
+
+        while let Some(entry) = archive.next_entry()? {
+            if entry.path.to_string_lossy().starts_with("data.tar") {
+                let mut payload_file = tempfile::NamedTempFile::new()?;
+                archive.read_data(&mut payload_file, &mut limits)?;
+
+                let payload_path = payload_file.into_temp_path();
+                Self::extract_archive(&payload_path, output_directory, extraction_filter)?;
+                payload_path.close()?;
+
+                return Ok(());
+            }
+        }
+
+        Err(anyhow!(
+            "The debian package does not contain a data archive"
+        ))
+    }
+
+    // abstraction for download_and_extract_asset
+    fn extract_compressed_file(
+        file_path: &Path,
+        format: &FileFormat,
+        file_base_name: &str,
+        output_directory: &Path,
+        extraction_filter: &ExtractionFilter,
+    ) -> Result<()> {
+        let mut archive = ArchiveReader::open(file_path)?;
+
+        if archive.next_entry()?.is_none() {
+            return Ok(());
+        }
+
+        // a compressed stream that is not an archive (ex. `tool.gz`) contains a single binary
+        if !archive.is_raw() {
+            return Self::extract_archive(file_path, output_directory, extraction_filter);
+        }
+
+        let decompressed_file_name = file_base_name
+            .strip_suffix(&format!(".{}", format.extension()))
+            .unwrap_or(file_base_name);
+
+        let mut output_path = output_directory.to_path_buf();
+        output_path.push(decompressed_file_name);
+
+        let mut output_file = File::create(output_path)?;
+        archive.read_data(&mut output_file, &mut ExtractionLimits::from_env()?)?;
 
         Ok(())
     }
@@ -356,38 +965,202 @@ impl PackageInstallation<'_> {
         Ok(())
     }
 
-    fn add_index_db_entry(&self) -> Result<()> {
+    fn add_index_db_entry(&self, asset: &Asset, checksum: &str) -> Result<()> {
         // stupid cargo formatter makes the code look horrendous (well, at least consistently horrendous)
         let mut statement =
-            self.index_db.prepare("INSERT INTO packages (repository, tag, lock, assetFilter, execRename, preReleases) VALUES(?, ?, ?, ?, ?, ?)")?;
-        statement
-            .bind(
-                1,
-                format!("{}/{}", self.repository_author, self.repository_name).as_str(),
-            )
-            .unwrap();
-        statement
-            .bind(2, self.selected_release.as_ref().unwrap().tag_name.as_str())
-            .unwrap();
-        statement.bind(3, self.lock_updates as i64).unwrap();
+            self.index_db.prepare("INSERT INTO packages (repository, tag, lock, assetFilter, exclusiveLinks, preReleases, stripComponents, includeGlobs, excludeGlobs, executableGlobs, nonExecutableGlobs, linkConflicts, shimEnvironment, shimArguments, installedAt, assetName, assetSize, assetUrl, assetChecksum) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), ?, ?, ?, ?)")?;
+        statement.bind(
+            1,
+            format!("{}/{}", self.repository_author, self.repository_name).as_str(),
+        )?;
+        statement.bind(
+            2,
+            self.selected_release
+                .as_ref()
+                .context("No release was selected prior to installation")?
+                .tag_name
+                .as_str(),
+        )?;
+        statement.bind(3, self.lock_updates as i64)?;
         match &self.asset_regex_filter {
-            Some(filter) => statement.bind(4, filter.as_str()).unwrap(),
-            None => statement.bind(4, &sqlite3::Value::Null).unwrap(),
+            Some(filter) => statement.bind(4, filter.as_str())?,
+            None => statement.bind(4, &sqlite3::Value::Null)?,
         };
-        match &self.rename_executable {
-            Some(rename) => statement
-                .bind(5, format!("{}/{}", rename.0, rename.1).as_str())
-                .unwrap(),
-            None => statement.bind(5, &sqlite3::Value::Null).unwrap(),
+        statement.bind(5, self.link_selection.exclusive as i64)?;
+        statement.bind(6, self.including_prerelease as i64)?;
+        statement.bind(7, self.extraction_filter.strip_components as i64)?;
+        match ExtractionFilter::serialize_patterns(&self.extraction_filter.include_patterns) {
+            Some(patterns) => statement.bind(8, patterns.as_str())?,
+            None => statement.bind(8, &sqlite3::Value::Null)?,
         };
-        statement.bind(6, self.including_prerelease as i64).unwrap();
+        match ExtractionFilter::serialize_patterns(&self.extraction_filter.exclude_patterns) {
+            Some(patterns) => statement.bind(9, patterns.as_str())?,
+            None => statement.bind(9, &sqlite3::Value::Null)?,
+        };
+        match ExtractionFilter::serialize_patterns(&self.executable_detector.executable_patterns) {
+            Some(patterns) => statement.bind(10, patterns.as_str())?,
+            None => statement.bind(10, &sqlite3::Value::Null)?,
+        };
+        match ExtractionFilter::serialize_patterns(
+            &self.executable_detector.non_executable_patterns,
+        ) {
+            Some(patterns) => statement.bind(11, patterns.as_str())?,
+            None => statement.bind(11, &sqlite3::Value::Null)?,
+        };
+        statement.bind(12, self.conflict_policy.persisted().as_str())?;
+        match self.shim.serialize_environment() {
+            Some(environment) => statement.bind(13, environment.as_str())?,
+            None => statement.bind(13, &sqlite3::Value::Null)?,
+        };
+        match self.shim.serialize_arguments() {
+            Some(arguments) => statement.bind(14, arguments.as_str())?,
+            None => statement.bind(14, &sqlite3::Value::Null)?,
+        };
+        statement.bind(15, asset.name.as_str())?;
+        statement.bind(16, asset.size)?;
+        statement.bind(17, asset.browser_download_url.as_str())?;
+        statement.bind(18, checksum)?;
 
-        loop {
-            if statement.next().unwrap() == sqlite3::State::Done {
-                break;
-            }
-        }
+        while statement.next()? != sqlite3::State::Done {}
 
         Ok(())
     }
+
+    // additional versions share the settings of the installed package
+    fn add_version_entry(&self, asset: &Asset, checksum: &str) -> Result<()> {
+        let mut statement =
+            self.index_db.prepare("INSERT INTO versions (repository, tag, installedAt, assetName, assetSize, assetUrl, assetChecksum) VALUES(?, ?, datetime('now'), ?, ?, ?, ?)")?;
+        statement.bind(
+            1,
+            format!("{}/{}", self.repository_author, self.repository_name).as_str(),
+        )?;
+        statement.bind(
+            2,
+            self.selected_release
+                .as_ref()
+                .context("No release was selected prior to installation")?
+                .tag_name
+                .as_str(),
+        )?;
+        statement.bind(3, asset.name.as_str())?;
+        statement.bind(4, asset.size)?;
+        statement.bind(5, asset.browser_download_url.as_str())?;
+        statement.bind(6, checksum)?;
+
+        while statement.next()? != sqlite3::State::Done {}
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_link;
+
+    // tar refuses to archive links through other links, so the archive is written by hand
+    fn write_symlink_archive(archive_path: &Path, symlinks: &[(&str, &str)]) {
+        let mut archive = Vec::new();
+
+        for (name, target) in symlinks {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[100..107].copy_from_slice(b"0000777");
+            header[108..115].copy_from_slice(b"0000000");
+            header[116..123].copy_from_slice(b"0000000");
+            header[124..135].copy_from_slice(b"00000000000");
+            header[136..147].copy_from_slice(b"00000000000");
+            header[148..156].copy_from_slice(b"        ");
+            header[156] = b'2';
+            header[157..157 + target.len()].copy_from_slice(target.as_bytes());
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[263..265].copy_from_slice(b"00");
+
+            let checksum = header.iter().map(|byte| *byte as u32).sum::<u32>();
+            header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+            archive.extend_from_slice(&header);
+        }
+
+        archive.extend_from_slice(&[0u8; 1024]);
+        std::fs::write(archive_path, archive).unwrap();
+    }
+
+    #[test]
+    fn extract_archive_skips_symlinks_placed_through_other_symlinks() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive_path = directory.path().join("chained.tar");
+        let output_directory = directory.path().join("package");
+        write_symlink_archive(&archive_path, &[("a", "."), ("a/b", "..")]);
+        create_dir_all(&output_directory).unwrap();
+
+        PackageInstallation::extract_archive(
+            &archive_path,
+            &output_directory,
+            &ExtractionFilter::default(),
+        )
+        .unwrap();
+
+        assert!(output_directory.join("a").is_symlink());
+        assert!(output_directory.join("b").symlink_metadata().is_err());
+        assert!(output_directory.join("a/b").symlink_metadata().is_err());
+    }
+
+    #[test]
+    fn extract_archive_skips_symlinks_resolving_through_other_symlinks() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive_path = directory.path().join("chained.tar");
+        let output_directory = directory.path().join("package");
+        write_symlink_archive(
+            &archive_path,
+            &[
+                ("y", "."),
+                ("x", "y/.."),
+                ("p1", "y"),
+                ("p2", "p1/.."),
+                ("p3", "p2/.."),
+            ],
+        );
+        create_dir_all(&output_directory).unwrap();
+
+        PackageInstallation::extract_archive(
+            &archive_path,
+            &output_directory,
+            &ExtractionFilter::default(),
+        )
+        .unwrap();
+
+        assert!(output_directory.join("y").is_symlink());
+        assert!(output_directory.join("p1").is_symlink());
+        assert!(output_directory.join("x").symlink_metadata().is_err());
+        assert!(output_directory.join("p2").symlink_metadata().is_err());
+
+        // `p2` is gone, so `p3` is left dangling rather than pointing outside of the package
+        let output_path = output_directory.canonicalize().unwrap();
+        assert!(output_directory
+            .join("p3")
+            .canonicalize()
+            .map_or(true, |resolved_path| resolved_path.starts_with(output_path)));
+    }
+
+    #[test]
+    fn extract_archive_keeps_symlinks_within_the_package() {
+        let directory = tempfile::tempdir().unwrap();
+        let archive_path = directory.path().join("links.tar");
+        let output_directory = directory.path().join("package");
+        write_symlink_archive(&archive_path, &[("bin/tool", "../lib/tool")]);
+        create_dir_all(&output_directory).unwrap();
+
+        PackageInstallation::extract_archive(
+            &archive_path,
+            &output_directory,
+            &ExtractionFilter::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_link(output_directory.join("bin/tool")).unwrap(),
+            PathBuf::from("../lib/tool")
+        );
+    }
 }
@@ -1,27 +1,87 @@
-use crate::common_directories;
+use crate::executables::{ExecutableDetector, LinkSelection};
+use crate::extraction_filter::ExtractionFilter;
+use crate::output::{self, OutputFormat};
+use crate::{common_directories, versions};
 use anyhow::{anyhow, Result};
-use file_format::{FileFormat, Kind};
-use walkdir::WalkDir;
+use serde::Serialize;
+use std::path::PathBuf;
 
-pub async fn list_repositories() -> Result<()> {
+#[derive(Serialize)]
+struct RepositoryEntry {
+    repository: String,
+    tag: String,
+    locked: bool,
+    prereleases: bool,
+    // installed next to `tag`, see `dyst use`
+    versions: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ExecutableEntry {
+    executable: String,
+    path: PathBuf,
+    // empty if the executable is ignored
+    links: Vec<String>,
+}
+
+pub async fn list_repositories(output: OutputFormat) -> Result<()> {
     let index_db = common_directories::open_database()?;
 
     let mut statement = index_db
-        .prepare("SELECT repository, tag FROM packages")
-        .unwrap();
-
-    while let sqlite3::State::Row = statement.next().unwrap() {
-        println!(
-            "{} {}",
-            statement.read::<String>(0).unwrap(),
-            statement.read::<String>(1).unwrap(),
-        );
+        .prepare("SELECT repository, tag, lock, preReleases FROM packages ORDER BY repository")?;
+    let mut repositories = Vec::new();
+
+    while let sqlite3::State::Row = statement.next()? {
+        let repository = statement.read::<String>(0)?;
+
+        repositories.push(RepositoryEntry {
+            versions: versions::list_versions(&index_db, &repository)?,
+            repository,
+            tag: statement.read::<String>(1)?,
+            locked: statement.read::<i64>(2)? != 0,
+            prereleases: statement.read::<i64>(3)? != 0,
+        });
+    }
+
+    match output {
+        // one line per repository, as scripts expect; additional versions are in the other formats
+        OutputFormat::Plain => {
+            for entry in repositories {
+                println!("{} {}", entry.repository, entry.tag);
+            }
+        }
+        OutputFormat::Table => output::print_table(
+            &[
+                "REPOSITORY",
+                "TAG",
+                "LOCKED",
+                "PRERELEASES",
+                "OTHER VERSIONS",
+            ],
+            &repositories
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.repository.clone(),
+                        entry.tag.clone(),
+                        entry.locked.to_string(),
+                        entry.prereleases.to_string(),
+                        entry.versions.join(", "),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&repositories)?,
     }
 
     Ok(())
 }
 
-pub fn list_executables(repository_author: &str, repository_name: &str) -> Result<()> {
+pub fn list_executables(
+    repository_author: &str,
+    repository_name: &str,
+    output: OutputFormat,
+) -> Result<()> {
     let package_store = common_directories::get_package_store()?;
 
     let mut asset_path = package_store.clone();
@@ -32,18 +92,54 @@ pub fn list_executables(repository_author: &str, repository_name: &str) -> Resul
         return Err(anyhow!("The requested repository is not installed"));
     }
 
-    for entry in WalkDir::new(&asset_path)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|entry| entry.file_type().is_file())
-    {
-        let path = entry.path();
-        let file_name = entry.file_name().to_str().unwrap();
-        let format = FileFormat::from_file(path)?;
-
-        if format.kind() == Kind::Executable {
-            println!("{}", file_name);
+    let index_db = common_directories::open_database()?;
+    let extraction_filter =
+        ExtractionFilter::from_index_db(&index_db, repository_author, repository_name)?;
+    let executable_detector =
+        ExecutableDetector::from_index_db(&index_db, repository_author, repository_name)?;
+    let link_selection =
+        LinkSelection::from_index_db(&index_db, repository_author, repository_name)?;
+
+    let mut executables = Vec::new();
+
+    for path in executable_detector.find_executables(&asset_path, &extraction_filter)? {
+        executables.push(ExecutableEntry {
+            executable: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            links: link_selection.link_names(&path, repository_name)?,
+            path,
+        });
+    }
+
+    match output {
+        OutputFormat::Plain => {
+            for entry in executables {
+                if entry.links.is_empty() {
+                    println!("{} (ignored)", entry.executable);
+                } else {
+                    println!("{} -> {}", entry.executable, entry.links.join(", "));
+                }
+            }
         }
+        OutputFormat::Table => output::print_table(
+            &["EXECUTABLE", "LINKS"],
+            &executables
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.executable.clone(),
+                        match entry.links.is_empty() {
+                            true => String::from("(ignored)"),
+                            false => entry.links.join(", "),
+                        },
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&executables)?,
     }
 
     Ok(())
@@ -1,3 +1,4 @@
+use crate::history::HistoryEvent;
 use anyhow::Result;
 
 pub async fn lock_package(
@@ -5,20 +6,7 @@ pub async fn lock_package(
     repository_author: &str,
     repository_name: &str,
 ) -> Result<()> {
-    let mut statement = index_db.prepare("UPDATE packages SET lock = ? WHERE repository = ?")?;
-    statement.bind(1, 1)?;
-    statement.bind(
-        2,
-        format!("{}/{}", repository_author, repository_name).as_str(),
-    )?;
-
-    loop {
-        if statement.next().unwrap() == sqlite3::State::Done {
-            break;
-        }
-    }
-
-    Ok(())
+    set_lock(index_db, repository_author, repository_name, true)
 }
 
 pub async fn unlock_package(
@@ -26,18 +14,29 @@ pub async fn unlock_package(
     repository_author: &str,
     repository_name: &str,
 ) -> Result<()> {
+    set_lock(index_db, repository_author, repository_name, false)
+}
+
+fn set_lock(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    lock: bool,
+) -> Result<()> {
+    let repository = format!("{}/{}", repository_author, repository_name);
+
     let mut statement = index_db.prepare("UPDATE packages SET lock = ? WHERE repository = ?")?;
-    statement.bind(1, 0)?;
-    statement.bind(
-        2,
-        format!("{}/{}", repository_author, repository_name).as_str(),
-    )?;
+    statement.bind(1, lock as i64)?;
+    statement.bind(2, repository.as_str())?;
 
-    loop {
-        if statement.next().unwrap() == sqlite3::State::Done {
-            break;
-        }
-    }
+    while statement.next()? != sqlite3::State::Done {}
 
-    Ok(())
+    HistoryEvent {
+        action: match lock {
+            true => "lock",
+            false => "unlock",
+        },
+        ..Default::default()
+    }
+    .record(index_db, &repository)
 }
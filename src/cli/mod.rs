@@ -1,8 +1,18 @@
+pub mod completions;
+pub mod config;
+pub mod doctor;
+pub mod history;
+pub mod info;
 pub mod install;
 pub mod list;
 pub mod lock;
+pub mod outdated;
 pub mod prereleases;
+pub mod profile;
 pub mod remove;
 pub mod rename;
+pub mod resolve;
+pub mod run;
 pub mod search;
 pub mod update;
+pub mod use_version;
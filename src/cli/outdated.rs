@@ -0,0 +1,100 @@
+use crate::cli::info::PackageInfo;
+use crate::config::Config;
+use crate::output::{self, OutputFormat};
+use crate::split_repository_argument;
+use anyhow::Result;
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct OutdatedEntry {
+    repository: String,
+    tag: String,
+    latest_tag: String,
+    locked: bool,
+}
+
+pub async fn list_outdated(index_db: &sqlite3::Connection, output: OutputFormat) -> Result<()> {
+    let mut statement = index_db
+        .prepare("SELECT repository, tag, lock, preReleases FROM packages ORDER BY repository")?;
+    let mut packages = Vec::new();
+
+    while let sqlite3::State::Row = statement.next()? {
+        packages.push((
+            statement.read::<String>(0)?,
+            statement.read::<String>(1)?,
+            statement.read::<i64>(2)? != 0,
+            statement.read::<i64>(3)? != 0,
+        ));
+    }
+
+    // the releases are fetched concurrently, but reported in order
+    let parallelism = Config::load()?.parallelism.unwrap_or(4).max(1);
+    let latest_tags = stream::iter(&packages)
+        .map(|(repository, _, _, prereleases)| async move {
+            let (repository_author, repository_name) = split_repository_argument(repository)?;
+
+            Ok::<_, anyhow::Error>(
+                PackageInfo::fetch_latest_tag(repository_author, repository_name, *prereleases)
+                    .await,
+            )
+        })
+        .buffered(parallelism)
+        .collect::<Vec<Result<Option<String>>>>()
+        .await;
+
+    let mut outdated = Vec::new();
+
+    for ((repository, tag, locked, _), latest_tag) in packages.into_iter().zip(latest_tags) {
+        let latest_tag = match latest_tag? {
+            Some(latest_tag) => latest_tag,
+            None => {
+                eprintln!(
+                    "Warning: The releases of '{}' could not be fetched.",
+                    repository
+                );
+                continue;
+            }
+        };
+
+        if latest_tag != tag {
+            outdated.push(OutdatedEntry {
+                repository,
+                tag,
+                latest_tag,
+                locked,
+            });
+        }
+    }
+
+    match output {
+        OutputFormat::Plain => {
+            for entry in outdated {
+                println!(
+                    "{} {} -> {}{}",
+                    entry.repository,
+                    entry.tag,
+                    entry.latest_tag,
+                    if entry.locked { " (locked)" } else { "" }
+                );
+            }
+        }
+        OutputFormat::Table => output::print_table(
+            &["REPOSITORY", "TAG", "LATEST", "LOCKED"],
+            &outdated
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.repository.clone(),
+                        entry.tag.clone(),
+                        entry.latest_tag.clone(),
+                        entry.locked.to_string(),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&outdated)?,
+    }
+
+    Ok(())
+}
@@ -13,11 +13,7 @@ pub async fn allow_prereleases(
         format!("{}/{}", repository_author, repository_name).as_str(),
     )?;
 
-    loop {
-        if statement.next().unwrap() == sqlite3::State::Done {
-            break;
-        }
-    }
+    while statement.next()? != sqlite3::State::Done {}
 
     Ok(())
 }
@@ -0,0 +1,127 @@
+use crate::common_directories;
+use crate::output::{self, OutputFormat};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fs::{create_dir_all, read_dir, remove_dir_all};
+use std::path::PathBuf;
+
+#[derive(Serialize)]
+struct ProfileEntry {
+    name: String,
+    active: bool,
+    binaries_path: PathBuf,
+}
+
+pub fn list_profiles(output: OutputFormat) -> Result<()> {
+    let active_profile = common_directories::get_profile();
+    let profiles_path = common_directories::get_profiles_path()?;
+
+    let mut profiles = vec![ProfileEntry {
+        name: String::from("default"),
+        active: active_profile.is_none(),
+        binaries_path: common_directories::get_default_executables_path()?,
+    }];
+
+    if profiles_path.is_dir() {
+        let mut names = Vec::new();
+        for entry in read_dir(&profiles_path)? {
+            let entry = entry?;
+
+            if entry.file_type()?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        names.sort();
+
+        for name in names {
+            profiles.push(ProfileEntry {
+                active: active_profile.as_ref() == Some(&name),
+                binaries_path: profiles_path.join(&name).join("bin"),
+                name,
+            });
+        }
+    }
+
+    match output {
+        OutputFormat::Plain => {
+            for profile in profiles {
+                println!(
+                    "{}{}",
+                    profile.name,
+                    if profile.active { " (active)" } else { "" }
+                );
+            }
+        }
+        OutputFormat::Table => output::print_table(
+            &["PROFILE", "ACTIVE", "BINARIES"],
+            &profiles
+                .iter()
+                .map(|profile| {
+                    vec![
+                        profile.name.clone(),
+                        profile.active.to_string(),
+                        profile.binaries_path.display().to_string(),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&profiles)?,
+    }
+
+    Ok(())
+}
+
+pub fn create_profile(name: &str) -> Result<()> {
+    common_directories::check_profile_name(name)?;
+
+    let profile_path = common_directories::get_profiles_path()?.join(name);
+    if profile_path.exists() {
+        return Err(anyhow!("The profile '{}' already exists", name));
+    }
+
+    for directory in ["store", "bin", "share"] {
+        create_dir_all(profile_path.join(directory))?;
+    }
+
+    println!(
+        "Created the profile '{}', add {} to your PATH to use its executables.",
+        name,
+        profile_path.join("bin").display()
+    );
+
+    Ok(())
+}
+
+// links, man pages and completions all live within the profile, so removing it leaves nothing
+// behind
+pub fn delete_profile(name: &str, force: bool) -> Result<()> {
+    let profile_path = common_directories::get_profile_path(name)?;
+    let database_path = profile_path.join("store").join("index.db3");
+
+    if database_path.exists() && !force {
+        let index_db = sqlite3::open(&database_path)?;
+        let mut statement = index_db.prepare("SELECT COUNT(*) FROM packages")?;
+        statement.next()?;
+
+        let package_count = statement.read::<i64>(0)?;
+        if package_count > 0 {
+            return Err(anyhow!(
+                "The profile '{}' still has {} packages installed (consider passing `--force`)",
+                name,
+                package_count
+            ));
+        }
+    }
+
+    remove_dir_all(&profile_path)?;
+
+    Ok(())
+}
+
+// for activating a profile, e.g. `export PATH="$(dyst --profile ci profile path):$PATH"`
+pub fn print_binaries_path() -> Result<()> {
+    println!("{}", common_directories::get_executables_path()?.display());
+
+    Ok(())
+}
@@ -1,6 +1,45 @@
-use crate::common_directories;
-use anyhow::Result;
-use std::fs::{read_dir, read_link, remove_dir, remove_dir_all, remove_file};
+use crate::common_directories::{self, Transaction};
+use crate::executables::LinkSelection;
+use crate::history::HistoryEvent;
+use crate::{appimage, links, manifest, versions};
+use anyhow::{anyhow, Result};
+use std::fs::remove_dir;
+
+// unlike `uninstall_package`, this is recorded in the history
+pub async fn remove_repository(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+) -> Result<()> {
+    let repository = format!("{}/{}", repository_author, repository_name);
+
+    let mut statement = index_db
+        .prepare("SELECT tag, assetName, assetChecksum FROM packages WHERE repository = ?")?;
+    statement.bind(1, repository.as_str())?;
+
+    if statement.next()? != sqlite3::State::Row {
+        return Err(anyhow!("The requested repository is not installed"));
+    }
+
+    let tag = statement.read::<String>(0)?;
+    let asset_name = statement.read::<String>(1).ok();
+    let checksum = statement.read::<String>(2).ok();
+
+    for version in versions::list_versions(index_db, &repository)? {
+        remove_version(index_db, repository_author, repository_name, &version)?;
+    }
+
+    uninstall_package(index_db, repository_author, repository_name).await?;
+
+    HistoryEvent {
+        action: "remove",
+        old_tag: Some(&tag),
+        asset_name: asset_name.as_deref(),
+        checksum: checksum.as_deref(),
+        ..Default::default()
+    }
+    .record(index_db, &repository)
+}
 
 pub async fn uninstall_package(
     index_db: &sqlite3::Connection,
@@ -13,47 +52,103 @@ pub async fn uninstall_package(
     );
 
     let package_store = common_directories::get_package_store()?;
-    let executables_path = common_directories::get_executables_path()?;
 
     let mut package_src_path = package_store.clone();
     package_src_path.push(repository_author);
     let package_parent_path = package_src_path.clone();
     package_src_path.push(repository_name);
 
+    let repository = format!("{}/{}", repository_author, repository_name);
+
+    // the database entries are only dropped once the files are gone
+    let transaction = Transaction::begin(index_db)?;
+
+    println!("  Deleting database entry");
+    let mut statement = index_db.prepare("DELETE FROM packages WHERE repository = ?")?;
+    statement.bind(1, repository.as_str())?;
+
+    while statement.next()? != sqlite3::State::Done {}
+
+    LinkSelection::delete(index_db, &repository)?;
+
+    println!("  Removing broken symlinks...");
+    links::remove_links(index_db, &repository, &package_src_path)?;
+
     println!("  Removing source directory...");
-    remove_dir_all(&package_src_path)?;
+    manifest::remove_package_files(index_db, &repository, &package_src_path)?;
 
     if package_parent_path.read_dir()?.next().is_none() {
         println!("  Removing empty parent directory");
         remove_dir(&package_parent_path)?;
     }
 
-    println!("  Deleting database entry");
-    let mut statement = index_db.prepare("DELETE FROM packages WHERE repository = ?")?;
-    statement.bind(
-        1,
-        format!("{}/{}", repository_author, repository_name).as_str(),
+    transaction.commit()?;
+
+    if appimage::has_desktop_entry(repository_author, repository_name)? {
+        println!("  Removing the AppImage's desktop entry...");
+        appimage::remove_desktop_entry(repository_author, repository_name)?;
+    }
+
+    println!("  Done.");
+
+    Ok(())
+}
+
+// a version installed next to the default one, see `dyst use`
+pub fn remove_version(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    tag: &str,
+) -> Result<()> {
+    let repository = format!("{}/{}", repository_author, repository_name);
+
+    let mut statement = index_db.prepare(
+        "SELECT assetName, assetChecksum FROM versions WHERE repository = ? AND tag = ?",
     )?;
+    statement.bind(1, repository.as_str())?;
+    statement.bind(2, tag)?;
 
-    loop {
-        if statement.next().unwrap() == sqlite3::State::Done {
-            break;
-        }
+    if statement.next()? != sqlite3::State::Row {
+        return Err(anyhow!("The requested version is not installed"));
     }
 
-    println!("  Removing broken symlinks...");
-    for entry in read_dir(executables_path)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if entry.file_type()?.is_symlink() {
-            let linked_path = read_link(&path)?;
-            if linked_path.starts_with(&package_src_path) {
-                remove_file(&path)?;
-            }
+    let asset_name = statement.read::<String>(0).ok();
+    let checksum = statement.read::<String>(1).ok();
+
+    println!("Uninstalling '{}' of '{}'...", tag, repository);
+
+    let version_key = versions::get_version_key(&repository, tag);
+    let version_path = versions::get_version_path(repository_author, repository_name, tag)?;
+    let transaction = Transaction::begin(index_db)?;
+
+    let mut statement =
+        index_db.prepare("DELETE FROM versions WHERE repository = ? AND tag = ?")?;
+    statement.bind(1, repository.as_str())?;
+    statement.bind(2, tag)?;
+    while statement.next()? != sqlite3::State::Done {}
+
+    links::remove_links(index_db, &version_key, &version_path)?;
+    manifest::remove_package_files(index_db, &version_key, &version_path)?;
+
+    // `.versions/author/name` and `.versions/author` are only kept while they are in use
+    for parent_path in version_path.ancestors().skip(1).take(2) {
+        if parent_path.read_dir()?.next().is_none() {
+            remove_dir(parent_path)?;
         }
     }
 
+    HistoryEvent {
+        action: "remove",
+        old_tag: Some(tag),
+        asset_name: asset_name.as_deref(),
+        checksum: checksum.as_deref(),
+        details: Some("side by side"),
+        ..Default::default()
+    }
+    .record(index_db, &repository)?;
+
+    transaction.commit()?;
     println!("  Done.");
 
     Ok(())
@@ -1,9 +1,12 @@
-use crate::common_directories;
+use crate::cli;
+use crate::common_directories::{self, Transaction};
+use crate::executables::{ExecutableDetector, LinkSelection};
+use crate::extraction_filter::ExtractionFilter;
+use crate::history::HistoryEvent;
+use crate::links::{self, ConflictPolicy};
+use crate::output::OutputFormat;
+use crate::shims::Shim;
 use anyhow::Result;
-use file_format::{FileFormat, Kind};
-use std::fs::{read_dir, read_link, remove_file};
-use symlink::symlink_file;
-use walkdir::WalkDir;
 
 pub async fn rename_executable(
     index_db: &sqlite3::Connection,
@@ -11,64 +14,71 @@ pub async fn rename_executable(
     repository_name: &str,
     old_executable_name: &str,
     new_executable_name: &str,
+    output: OutputFormat,
 ) -> Result<()> {
     let package_store = common_directories::get_package_store()?;
-    let executables_path = common_directories::get_executables_path()?;
+    let repository = format!("{}/{}", repository_author, repository_name);
 
     let mut package_src_path = package_store.clone();
     package_src_path.push(repository_author);
     package_src_path.push(repository_name);
 
-    for entry in read_dir(&executables_path)? {
-        let entry = entry?;
-        let path = entry.path();
+    let extraction_filter =
+        ExtractionFilter::from_index_db(index_db, repository_author, repository_name)?;
+    let executable_detector =
+        ExecutableDetector::from_index_db(index_db, repository_author, repository_name)?;
+    let mut link_selection =
+        LinkSelection::from_index_db(index_db, repository_author, repository_name)?;
+    link_selection.rename(old_executable_name, new_executable_name);
 
-        if entry.file_type()?.is_symlink() {
-            let linked_path = read_link(&path)?;
-            if linked_path.starts_with(&package_src_path) {
-                remove_file(&path)?;
-            }
+    let mut planned_links = Vec::new();
+    for path in executable_detector.find_executables(&package_src_path, &extraction_filter)? {
+        for link_name in link_selection.link_names(&path, repository_name)? {
+            planned_links.push((path.clone(), link_name));
         }
     }
 
-    for entry in WalkDir::new(&package_src_path)
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter(|entry| entry.file_type().is_file())
-    {
-        let path = entry.path();
-        let format = FileFormat::from_file(path)?;
+    // conflicts are resolved before the current links are removed, so that an abort keeps them
+    let resolved_links = links::resolve_conflicts(
+        index_db,
+        &repository,
+        repository_author,
+        &package_src_path,
+        planned_links,
+        ConflictPolicy::from_index_db(index_db, repository_author, repository_name)?,
+        // forcing is never persisted, so only the package's own untracked links are replaced
+        &mut Vec::new(),
+    )?;
 
-        if format.kind() == Kind::Executable {
-            let mut binary_path = executables_path.clone();
-            let file_name = entry.file_name().to_str().unwrap();
+    let shim = Shim::from_index_db(index_db, repository_author, repository_name)?;
 
-            if old_executable_name == file_name {
-                binary_path.push(new_executable_name);
-            } else {
-                binary_path.push(file_name);
-            }
+    let transaction = Transaction::begin(index_db)?;
 
-            symlink_file(path, binary_path)?;
-        }
+    links::remove_executable_links(index_db, &repository, &package_src_path)?;
+    for (target, link_path) in resolved_links {
+        links::create_link(index_db, &repository, &target, &link_path, &shim)?;
     }
 
+    link_selection.save(index_db, repository_author, repository_name)?;
+
+    // the rule has been carried over into `executableLinks`
     let mut statement =
-        index_db.prepare("UPDATE packages SET execRename = ? WHERE repository = ?")?;
-    statement.bind(
-        1,
-        format!("{}/{}", old_executable_name, new_executable_name).as_str(),
-    )?;
-    statement.bind(
-        2,
-        format!("{}/{}", repository_author, repository_name).as_str(),
-    )?;
+        index_db.prepare("UPDATE packages SET execRename = NULL WHERE repository = ?")?;
+    statement.bind(1, repository.as_str())?;
 
-    loop {
-        if statement.next().unwrap() == sqlite3::State::Done {
-            break;
-        }
+    while statement.next()? != sqlite3::State::Done {}
+
+    HistoryEvent {
+        action: "rename",
+        details: Some(&format!(
+            "{} -> {}",
+            old_executable_name, new_executable_name
+        )),
+        ..Default::default()
     }
+    .record(index_db, &repository)?;
+
+    transaction.commit()?;
 
-    Ok(())
+    cli::list::list_executables(repository_author, repository_name, output)
 }
@@ -0,0 +1,139 @@
+use crate::config::Config;
+use crate::executables::{ExecutableDetector, LinkSelection};
+use crate::extraction_filter::ExtractionFilter;
+use crate::{common_directories, version_files, versions};
+use anyhow::{anyhow, Result};
+use std::env;
+use std::io;
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// prints the directory of the pinned version and, for the launcher scripts, the path of the given
+// executable within it on a second line
+pub fn resolve_version(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    executable: Option<&Path>,
+) -> Result<()> {
+    let repository = format!("{}/{}", repository_author, repository_name);
+
+    let mut statement = index_db.prepare("SELECT tag FROM packages WHERE repository = ?")?;
+    statement.bind(1, repository.as_str())?;
+
+    if statement.next()? != sqlite3::State::Row {
+        return Err(anyhow!("The requested repository is not installed"));
+    }
+
+    let default_tag = statement.read::<String>(0)?;
+
+    let package_path = match version_files::find_pinned_version(&repository)? {
+        Some((tag, version_file)) if tag != default_tag => {
+            if !versions::is_version_installed(index_db, &repository, &tag)? {
+                if !Config::load()?.install_pinned_versions.unwrap_or(false) {
+                    return Err(anyhow!(
+                        "'{}' of '{}' is pinned in {}, but not installed (consider `dyst install {} --tag {}` or `dyst config set install-pinned-versions true`)",
+                        tag,
+                        repository,
+                        version_file.display(),
+                        repository,
+                        tag
+                    ));
+                }
+
+                install_version(&repository, &tag)?;
+            }
+
+            versions::get_version_path(repository_author, repository_name, &tag)?
+        }
+        _ => common_directories::get_package_store()?
+            .join(repository_author)
+            .join(repository_name),
+    };
+
+    println!("{}", package_path.display());
+
+    if let Some(executable) = executable {
+        let executable_path = find_version_executable(
+            index_db,
+            repository_author,
+            repository_name,
+            &package_path,
+            executable,
+        )?;
+
+        println!("{}", executable_path.display());
+    }
+
+    Ok(())
+}
+
+// versions often name their top-level directory after the tag, so an executable of the default
+// version is looked up by the names it is linked under unless the same path exists
+fn find_version_executable(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    package_path: &Path,
+    executable: &Path,
+) -> Result<PathBuf> {
+    let extraction_filter =
+        ExtractionFilter::from_index_db(index_db, repository_author, repository_name)?;
+    let executable_detector =
+        ExecutableDetector::from_index_db(index_db, repository_author, repository_name)?;
+    let link_selection =
+        LinkSelection::from_index_db(index_db, repository_author, repository_name)?;
+
+    let executables = executable_detector.find_executables(package_path, &extraction_filter)?;
+
+    let executable_path = package_path.join(executable);
+    if executables.contains(&executable_path) {
+        return Ok(executable_path);
+    }
+
+    let default_path = common_directories::get_package_store()?
+        .join(repository_author)
+        .join(repository_name)
+        .join(executable);
+
+    link_selection
+        .find_counterpart(&executables, &default_path, repository_name)?
+        .ok_or_else(|| {
+            anyhow!(
+                "The pinned version of '{}/{}' provides no executable like '{}'",
+                repository_author,
+                repository_name,
+                executable.display()
+            )
+        })
+}
+
+// a regular `dyst install`, which takes the lock on the package store and reports its progress on
+// stderr, as stdout is read by the launcher script
+fn install_version(repository: &str, tag: &str) -> Result<()> {
+    let mut command = Command::new(env::current_exe()?);
+
+    if common_directories::is_system_wide() {
+        command.arg("--system");
+    }
+
+    if let Some(profile) = common_directories::get_profile() {
+        command.args(["--profile", &profile]);
+    }
+
+    let status = command
+        .args(["install", repository, "--tag", tag])
+        .stdout(Stdio::from(io::stderr().as_fd().try_clone_to_owned()?))
+        .status()?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "The pinned version '{}' of '{}' could not be installed",
+            tag,
+            repository
+        ));
+    }
+
+    Ok(())
+}
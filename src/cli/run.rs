@@ -0,0 +1,136 @@
+use crate::cli::install::PackageInstallation;
+use crate::executables::LinkSelection;
+use crate::{common_directories, versions};
+use anyhow::{anyhow, Result};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// the directory the release has been extracted into, which is the cache unless it is kept
+pub async fn prepare_release(
+    index_db: &sqlite3::Connection,
+    installer: &mut PackageInstallation<'_>,
+    repository_author: &str,
+    repository_name: &str,
+    tag: Option<&str>,
+    keep: bool,
+) -> Result<PathBuf> {
+    match keep {
+        true => install_release(index_db, installer, repository_author, repository_name, tag).await,
+        false => {
+            installer.fetch_release().await?;
+            installer.extract_to_cache().await
+        }
+    }
+}
+
+// replaces dyst with the executable, so that its exit code and signals are passed on as is
+pub fn run_executable(
+    installer: &PackageInstallation<'_>,
+    package_path: &Path,
+    repository_name: &str,
+    executable: Option<&str>,
+    arguments: &[String],
+) -> Result<()> {
+    let executable_path = select_executable(
+        installer.find_executables(package_path)?,
+        repository_name,
+        executable,
+    )?;
+    PackageInstallation::make_executable(&executable_path)?;
+
+    let error = Command::new(&executable_path)
+        .args(arguments)
+        .env("DYST_PACKAGE", package_path)
+        .exec();
+
+    Err(anyhow!(error).context(format!(
+        "{} could not be executed",
+        executable_path.display()
+    )))
+}
+
+// with `--keep`, the release is installed like `dyst install` would, next to the installed
+// version if the repository is installed already
+async fn install_release(
+    index_db: &sqlite3::Connection,
+    installer: &mut PackageInstallation<'_>,
+    repository_author: &str,
+    repository_name: &str,
+    tag: Option<&str>,
+) -> Result<PathBuf> {
+    let _store_lock = common_directories::lock_package_store()?;
+    let repository = format!("{}/{}", repository_author, repository_name);
+    let package_path = common_directories::get_package_store()?
+        .join(repository_author)
+        .join(repository_name);
+
+    let mut statement = index_db.prepare("SELECT tag FROM packages WHERE repository = ?")?;
+    statement.bind(1, repository.as_str())?;
+
+    if statement.next()? != sqlite3::State::Row {
+        installer.fetch_release().await?;
+        installer.install().await?;
+
+        return Ok(package_path);
+    }
+
+    let default_tag = statement.read::<String>(0)?;
+    // the executables are found the way they were when the package was installed
+    installer.restore_settings()?;
+
+    match tag {
+        Some(tag) if tag != default_tag => {
+            if !versions::is_version_installed(index_db, &repository, tag)? {
+                installer.side_by_side(true);
+                installer.fetch_release().await?;
+                installer.install().await?;
+            }
+
+            versions::get_version_path(repository_author, repository_name, tag)
+        }
+        _ => Ok(package_path),
+    }
+}
+
+// executables are matched by the name they would be linked under, falling back to the only one
+fn select_executable(
+    executables: Vec<PathBuf>,
+    repository_name: &str,
+    executable: Option<&str>,
+) -> Result<PathBuf> {
+    let link_selection = LinkSelection::default();
+    let mut named_executables = Vec::new();
+
+    for path in executables {
+        for link_name in link_selection.link_names(&path, repository_name)? {
+            named_executables.push((link_name, path.clone()));
+        }
+    }
+
+    let wanted_name = executable.unwrap_or(repository_name);
+
+    if let Some((_, path)) = named_executables
+        .iter()
+        .find(|(link_name, _)| link_name == wanted_name)
+    {
+        return Ok(path.clone());
+    }
+
+    match (executable, &named_executables[..]) {
+        (None, [(_, path)]) => Ok(path.clone()),
+        (_, []) => Err(anyhow!("The release does not contain any executables")),
+        _ => Err(anyhow!(
+            "{} (consider passing `--bin` with one of {})",
+            match executable {
+                Some(executable) => format!("The release does not contain '{}'", executable),
+                None => String::from("The executable to run could not be determined"),
+            },
+            named_executables
+                .iter()
+                .map(|(link_name, _)| link_name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )),
+    }
+}
@@ -1,20 +1,57 @@
+use crate::output::{self, OutputFormat};
 use anyhow::Result;
+use serde::Serialize;
 
-pub async fn search_repositories(query: &str) -> Result<()> {
+#[derive(Serialize)]
+struct SearchEntry {
+    repository: String,
+    url: String,
+    description: Option<String>,
+}
+
+pub async fn search_repositories(query: &str, output: OutputFormat) -> Result<()> {
     let page = octocrab::instance()
         .search()
         .repositories(query)
         .send()
         .await?;
 
-    for repository in page.into_iter() {
-        if repository.releases_url.is_some() {
-            println!(
-                "https://github.com/{} - {}",
-                repository.full_name.unwrap(),
-                repository.description.unwrap_or(String::from("n/a"))
-            );
+    let results = page
+        .into_iter()
+        .filter(|repository| repository.releases_url.is_some())
+        .filter_map(|repository| {
+            // the host differs on GitHub Enterprise
+            Some(SearchEntry {
+                url: repository.html_url?.to_string(),
+                repository: repository.full_name?,
+                description: repository.description,
+            })
+        })
+        .collect::<Vec<SearchEntry>>();
+
+    match output {
+        OutputFormat::Plain => {
+            for entry in results {
+                println!(
+                    "{} - {}",
+                    entry.url,
+                    entry.description.unwrap_or(String::from("n/a"))
+                );
+            }
         }
+        OutputFormat::Table => output::print_table(
+            &["REPOSITORY", "DESCRIPTION"],
+            &results
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.repository.clone(),
+                        entry.description.clone().unwrap_or(String::from("n/a")),
+                    ]
+                })
+                .collect::<Vec<Vec<String>>>(),
+        ),
+        OutputFormat::Json => output::print_json(&results)?,
     }
 
     Ok(())
@@ -1,21 +1,28 @@
+use crate::appimage;
 use crate::cli;
+use crate::config::Config;
 use crate::split_repository_argument;
-use anyhow::{Context, Result};
-use regex::Regex;
+use anyhow::{anyhow, Context, Result};
 
 pub async fn update_repositories(index_db: &sqlite3::Connection) -> Result<()> {
-    let mut statement = index_db
-        .prepare("SELECT repository, tag, lock, assetFilter, execRename, preReleases FROM packages")
-        .unwrap();
+    let config = Config::load()?;
 
-    while let sqlite3::State::Row = statement.next().unwrap() {
-        let repository = statement.read::<String>(0).unwrap();
-        let tag = statement.read::<String>(1).unwrap();
-        let lock = statement.read::<i64>(2).unwrap() != 0;
-        let asset_filter = statement.read::<String>(3);
-        let exec_rename = statement.read::<String>(4);
-        let prereleases = statement.read::<i64>(5).unwrap() != 0;
+    // the rows are read up front, updating a package rewrites them
+    let mut packages = Vec::new();
+    let mut statement = index_db.prepare("SELECT repository, tag, lock FROM packages")?;
 
+    while let sqlite3::State::Row = statement.next()? {
+        packages.push((
+            statement.read::<String>(0)?,
+            statement.read::<String>(1)?,
+            statement.read::<i64>(2)? != 0,
+        ));
+    }
+
+    // a package that fails to update keeps its installed release and doesn't hold up the others
+    let mut failed_repositories = Vec::new();
+
+    for (repository, tag, lock) in packages {
         if lock {
             println!(
                 "Warning: '{}' is locked and will not be updated.",
@@ -24,42 +31,59 @@ pub async fn update_repositories(index_db: &sqlite3::Connection) -> Result<()> {
             continue;
         }
 
-        let (repository_author, repository_name) = split_repository_argument(&repository)?;
-
-        let mut installer =
-            cli::install::PackageInstallation::new(&index_db, repository_author, repository_name);
-        installer.prereleases(prereleases);
-        installer.fetch_release().await?;
+        if let Err(error) = update_package(index_db, &config, &repository, tag).await {
+            eprintln!("Error: '{}' could not be updated: {:#}", repository, error);
+            failed_repositories.push(repository);
+        }
+    }
 
-        if asset_filter.is_ok() {
-            let regular_expression = Regex::new(&asset_filter.as_ref().unwrap())
-                .context("The asset filter contains illegal regex syntax")?;
+    if !failed_repositories.is_empty() {
+        return Err(anyhow!(
+            "The following repositories could not be updated: {}",
+            failed_repositories.join(", ")
+        ));
+    }
 
-            installer.asset_regex_filter(regular_expression);
-        }
+    Ok(())
+}
 
-        if exec_rename.is_ok() {
-            let search_replace = exec_rename.as_ref().unwrap();
-            let mut search_replace_split_iterator = search_replace.split('/');
-            let search = search_replace_split_iterator.next().unwrap();
-            let replace = search_replace_split_iterator.next().unwrap();
+async fn update_package(
+    index_db: &sqlite3::Connection,
+    config: &Config,
+    repository: &str,
+    tag: String,
+) -> Result<()> {
+    let (repository_author, repository_name) = split_repository_argument(repository)?;
 
-            installer.rename_executable(search.to_string(), replace.to_string());
-        }
+    let mut installer =
+        cli::install::PackageInstallation::new(index_db, repository_author, repository_name);
+    installer.restore_settings()?;
+    if let Some(libc) = config.libc {
+        installer.preferred_libc(libc);
+    }
+    installer.fetch_release().await?;
 
-        let release = installer.selected_release.as_ref().unwrap();
+    let release = installer
+        .selected_release
+        .as_ref()
+        .context("No release was selected prior to installation")?;
 
-        if release.tag_name != tag {
-            println!(
-                "Updating '{}' from '{}' to '{}'...",
-                repository, tag, release.tag_name
-            );
-            cli::remove::uninstall_package(&index_db, repository_author, repository_name).await?;
-            installer.install().await?;
-        } else {
-            println!("'{}' is up to date.", repository);
-        }
+    if release.tag_name == tag {
+        println!("'{}' is up to date.", repository);
+        return Ok(());
     }
 
-    Ok(())
+    println!(
+        "Updating '{}' from '{}' to '{}'...",
+        repository, tag, release.tag_name
+    );
+    installer.desktop_entry(appimage::has_desktop_entry(
+        repository_author,
+        repository_name,
+    )?);
+
+    // the installed release is only replaced once the new one has been downloaded and extracted,
+    // and is put back if linking it fails
+    installer.replaces(tag);
+    installer.install().await
 }
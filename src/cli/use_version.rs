@@ -0,0 +1,245 @@
+use crate::common_directories::{self, Transaction};
+use crate::executables::{ExecutableDetector, LinkSelection};
+use crate::extraction_filter::ExtractionFilter;
+use crate::history::HistoryEvent;
+use crate::links::{self, ConflictPolicy};
+use crate::shims::Shim;
+use crate::{manifest, shared_files, versions};
+use anyhow::{anyhow, Result};
+use std::fs::{create_dir_all, rename};
+use std::path::{Path, PathBuf};
+
+// puts both versions and their links back where they were if switching fails halfway; the
+// database is rolled back with the transaction, which is dropped afterwards
+struct SwitchErrorCleanup<'a> {
+    index_db: &'a sqlite3::Connection,
+    repository: &'a str,
+    package_path: &'a Path,
+    old_default_key: &'a str,
+    old_default_path: &'a Path,
+    new_default_path: &'a Path,
+    saved_links: Vec<(PathBuf, links::SavedLink)>,
+    moved_default: bool,
+    moved_version: bool,
+    persist: bool,
+}
+
+impl Drop for SwitchErrorCleanup<'_> {
+    fn drop(&mut self) {
+        if self.persist {
+            return;
+        }
+
+        // errors are ignored, so that as much as possible is put back
+        let _ = links::remove_links(self.index_db, self.repository, self.package_path);
+        let _ = links::remove_links(self.index_db, self.old_default_key, self.old_default_path);
+
+        if self.moved_version {
+            let _ = rename(self.package_path, self.new_default_path);
+        }
+        if self.moved_default {
+            let _ = rename(self.old_default_path, self.package_path);
+        }
+
+        let _ = links::restore_links(&self.saved_links);
+    }
+}
+
+// the installed version and the requested one swap places, so that the requested one is linked
+// under the plain names and everything else keeps working on `author/name` as before
+pub async fn use_version(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    tag: &str,
+) -> Result<()> {
+    let repository = format!("{}/{}", repository_author, repository_name);
+
+    let mut statement = index_db.prepare("SELECT tag FROM packages WHERE repository = ?")?;
+    statement.bind(1, repository.as_str())?;
+
+    if statement.next()? != sqlite3::State::Row {
+        return Err(anyhow!("The requested repository is not installed"));
+    }
+
+    let default_tag = statement.read::<String>(0)?;
+
+    if default_tag == tag {
+        println!("'{}' is already the default version", tag);
+        return Ok(());
+    }
+
+    if !versions::is_version_installed(index_db, &repository, tag)? {
+        return Err(anyhow!(
+            "The requested version is not installed (consider `dyst install {} --tag {}`)",
+            repository,
+            tag
+        ));
+    }
+
+    println!(
+        "Switching '{}' from '{}' to '{}'...",
+        repository, default_tag, tag
+    );
+
+    let package_path = common_directories::get_package_store()?
+        .join(repository_author)
+        .join(repository_name);
+    let old_default_key = versions::get_version_key(&repository, &default_tag);
+    let old_default_path =
+        versions::get_version_path(repository_author, repository_name, &default_tag)?;
+    let new_default_key = versions::get_version_key(&repository, tag);
+    let new_default_path = versions::get_version_path(repository_author, repository_name, tag)?;
+
+    let transaction = Transaction::begin(index_db)?;
+    let mut errdefer = SwitchErrorCleanup {
+        index_db,
+        repository: &repository,
+        package_path: &package_path,
+        old_default_key: &old_default_key,
+        old_default_path: &old_default_path,
+        new_default_path: &new_default_path,
+        saved_links: links::save_links(index_db, &repository, &package_path)?,
+        moved_default: false,
+        moved_version: false,
+        persist: false,
+    };
+    errdefer.saved_links.extend(links::save_links(
+        index_db,
+        &new_default_key,
+        &new_default_path,
+    )?);
+
+    println!("  Removing the links of both versions...");
+    links::remove_links(index_db, &repository, &package_path)?;
+    links::remove_links(index_db, &new_default_key, &new_default_path)?;
+    manifest::forget_package_files(index_db, &repository)?;
+    manifest::forget_package_files(index_db, &new_default_key)?;
+
+    println!("  Updating the database entries");
+    swap_index_db_entries(index_db, &repository, tag)?;
+
+    println!("  Moving the package directories...");
+    if let Some(parent) = old_default_path.parent() {
+        create_dir_all(parent)?;
+    }
+    rename(&package_path, &old_default_path)?;
+    errdefer.moved_default = true;
+    rename(&new_default_path, &package_path)?;
+    errdefer.moved_version = true;
+
+    println!("  Creating symlinks to the executables...");
+    link_version(
+        index_db,
+        repository_author,
+        repository_name,
+        &repository,
+        &package_path,
+        "",
+        &mut errdefer.saved_links,
+    )?;
+    link_version(
+        index_db,
+        repository_author,
+        repository_name,
+        &old_default_key,
+        &old_default_path,
+        &versions::get_link_suffix(&default_tag),
+        &mut errdefer.saved_links,
+    )?;
+
+    HistoryEvent {
+        action: "use",
+        old_tag: Some(&default_tag),
+        new_tag: Some(tag),
+        ..Default::default()
+    }
+    .record(index_db, &repository)?;
+
+    transaction.commit()?;
+    errdefer.persist = true;
+    println!("  Done.");
+
+    Ok(())
+}
+
+// the settings of the package stay, only the details of the installed release are exchanged
+fn swap_index_db_entries(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    tag: &str,
+) -> Result<()> {
+    for query in [
+        "INSERT INTO versions (repository, tag, installedAt, assetName, assetSize, assetUrl, assetChecksum) SELECT repository, tag, installedAt, assetName, assetSize, assetUrl, assetChecksum FROM packages WHERE repository = ?1",
+        "UPDATE packages SET (tag, installedAt, assetName, assetSize, assetUrl, assetChecksum) = (SELECT tag, installedAt, assetName, assetSize, assetUrl, assetChecksum FROM versions WHERE repository = ?1 AND tag = ?2) WHERE repository = ?1",
+        "DELETE FROM versions WHERE repository = ?1 AND tag = ?2",
+    ] {
+        let mut statement = index_db.prepare(query)?;
+        statement.bind(1, repository)?;
+        if query.contains("?2") {
+            statement.bind(2, tag)?;
+        }
+
+        while statement.next()? != sqlite3::State::Done {}
+    }
+
+    Ok(())
+}
+
+// only the default version provides man pages and shell completions
+fn link_version(
+    index_db: &sqlite3::Connection,
+    repository_author: &str,
+    repository_name: &str,
+    owner: &str,
+    package_path: &Path,
+    link_suffix: &str,
+    replaced_links: &mut Vec<(PathBuf, links::SavedLink)>,
+) -> Result<()> {
+    let extraction_filter =
+        ExtractionFilter::from_index_db(index_db, repository_author, repository_name)?;
+    let executable_detector =
+        ExecutableDetector::from_index_db(index_db, repository_author, repository_name)?;
+    let link_selection =
+        LinkSelection::from_index_db(index_db, repository_author, repository_name)?;
+    let shim = Shim::from_index_db(index_db, repository_author, repository_name)?;
+
+    manifest::record_package_files(index_db, owner, package_path)?;
+
+    let mut planned_links = Vec::new();
+    for path in executable_detector.find_executables(package_path, &extraction_filter)? {
+        for link_name in link_selection.link_names(&path, repository_name)? {
+            planned_links.push((path.clone(), format!("{}{}", link_name, link_suffix)));
+        }
+    }
+
+    for (target, link_path) in links::resolve_conflicts(
+        index_db,
+        owner,
+        repository_author,
+        package_path,
+        planned_links,
+        ConflictPolicy::from_index_db(index_db, repository_author, repository_name)?,
+        replaced_links,
+    )? {
+        links::create_link(index_db, owner, &target, &link_path, &shim)?;
+    }
+
+    if !link_suffix.is_empty() {
+        return Ok(());
+    }
+
+    for (target, link_path) in shared_files::find_shared_files(package_path, &extraction_filter)? {
+        if link_path.symlink_metadata().is_ok() {
+            println!(
+                "  Warning: Skipping '{}', which already exists",
+                link_path.display()
+            );
+            continue;
+        }
+
+        links::create_shared_link(index_db, owner, &target, &link_path)?;
+    }
+
+    Ok(())
+}
@@ -1,12 +1,95 @@
-use anyhow::Result;
+use crate::config::Config;
+use crate::migrations;
+use anyhow::{anyhow, Result};
 use microxdg::{Xdg, XdgApp};
+use regex::Regex;
 use std::env;
-use std::path::PathBuf;
+use std::fs::{create_dir_all, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+// set by `--system` before any path is looked up
+static SYSTEM_WIDE: AtomicBool = AtomicBool::new(false);
+
+pub fn use_system_wide_installation() {
+    SYSTEM_WIDE.store(true, Ordering::Relaxed);
+}
+
+pub fn is_system_wide() -> bool {
+    SYSTEM_WIDE.load(Ordering::Relaxed)
+}
+
+// set by `--profile`, which takes precedence over `DYST_PROFILE`
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+pub fn use_profile(profile: String) {
+    let _ = PROFILE.set(profile); // ignore error, only set once
+}
+
+// `None` is the default profile
+pub fn get_profile() -> Option<String> {
+    PROFILE
+        .get()
+        .cloned()
+        .or_else(|| env::var("DYST_PROFILE").ok())
+        .filter(|profile| !profile.is_empty() && profile != "default")
+}
+
+pub fn get_profiles_path() -> Result<PathBuf> {
+    match is_system_wide() {
+        true => Ok(PathBuf::from("/opt/dyst-profiles")),
+        false => Ok(Xdg::new()?.data()?.join("dyst-profiles")),
+    }
+}
+
+// profile names become directory names, whether they come from `--profile`, `DYST_PROFILE` or
+// `dyst profile create`
+pub fn check_profile_name(name: &str) -> Result<()> {
+    if name == "default" || !Regex::new(r"^[A-Za-z0-9_-]+$")?.is_match(name) {
+        return Err(anyhow!(
+            "The profile name '{}' is invalid (expected letters, digits, `-` and `_`, but not `default`)",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+// a profile has its own `store`, `bin` and `share` directories, so that man-db finds its man
+// pages through `bin` in `PATH`
+pub fn get_profile_path(profile: &str) -> Result<PathBuf> {
+    check_profile_name(profile)?;
+
+    let profile_path = get_profiles_path()?.join(profile);
+
+    if !profile_path.is_dir() {
+        return Err(anyhow!(
+            "The profile '{}' does not exist (consider `dyst profile create {}`)",
+            profile,
+            profile
+        ));
+    }
+
+    Ok(profile_path)
+}
 
 pub fn get_package_store() -> Result<PathBuf> {
+    if let Some(profile) = get_profile() {
+        return Ok(get_profile_path(&profile)?.join("store"));
+    }
+
     match env::var("DYST_PACKAGE_STORE") {
         Ok(package_store) => return Ok(PathBuf::from(package_store)),
         Err(_) => {
+            if let Some(package_store) = Config::load()?.package_store {
+                return Ok(package_store);
+            }
+
+            if is_system_wide() {
+                return Ok(PathBuf::from("/opt/dyst"));
+            }
+
             let xdg = XdgApp::new("dyst")?;
             let config_dir = xdg.app_data()?;
 
@@ -16,35 +99,172 @@ pub fn get_package_store() -> Result<PathBuf> {
 }
 
 pub fn get_executables_path() -> Result<PathBuf> {
+    if let Some(profile) = get_profile() {
+        return Ok(get_profile_path(&profile)?.join("bin"));
+    }
+
+    get_default_executables_path()
+}
+
+pub fn get_default_executables_path() -> Result<PathBuf> {
     match env::var("DYST_BINARIES_PATH") {
         Ok(package_store) => return Ok(PathBuf::from(package_store)),
-        Err(_) => return Ok(Xdg::new()?.exec()),
+        Err(_) => match Config::load()?.binaries_path {
+            Some(binaries_path) => Ok(binaries_path),
+            None if is_system_wide() => Ok(PathBuf::from("/usr/local/bin")),
+            None => Ok(Xdg::new()?.exec()),
+        },
+    }
+}
+
+// the base of the man page, shell completion and desktop entry directories
+pub fn get_shared_data_path() -> Result<PathBuf> {
+    if let Some(profile) = get_profile() {
+        return Ok(get_profile_path(&profile)?.join("share"));
+    }
+
+    match env::var("DYST_DATA_PATH") {
+        Ok(data_path) => Ok(PathBuf::from(data_path)),
+        Err(_) if is_system_wide() => Ok(PathBuf::from("/usr/local/share")),
+        Err(_) => Ok(Xdg::new()?.data()?),
+    }
+}
+
+pub fn get_cache_path() -> Result<PathBuf> {
+    match is_system_wide() {
+        true => Ok(PathBuf::from("/var/cache/dyst")),
+        false => Ok(XdgApp::new("dyst")?.app_cache()?),
+    }
+}
+
+pub fn get_config_path() -> Result<PathBuf> {
+    match is_system_wide() {
+        true => Ok(PathBuf::from("/etc/dyst/config.toml")),
+        false => Ok(XdgApp::new("dyst")?.app_config_file("config.toml")?),
+    }
+}
+
+pub fn get_executable_search_path(asset_path: &Path) -> PathBuf {
+    // unpacked .deb and .rpm packages mirror the filesystem hierarchy
+    let package_binaries_path = asset_path.join("usr").join("bin");
+
+    if package_binaries_path.is_dir() {
+        return package_binaries_path;
     }
+
+    asset_path.to_path_buf()
 }
 
 pub fn open_database() -> Result<sqlite3::Connection> {
     let mut package_store = get_package_store()?;
     package_store.push("index.db3");
 
-    let connection = sqlite3::open(package_store)?;
-
-    connection.execute(
-        "
-        CREATE TABLE IF NOT EXISTS packages (
-            repository TEXT PRIMARY KEY UNIQUE,
-            tag TEXT NOT NULL,
-            lock INTEGER NOT NULL,
-            assetFilter TEXT,
-            execRename TEXT,
-            preReleases INTEGER NOT NULL
-        );
-        CREATE TABLE IF NOT EXISTS dyst (
-            key TEXT PRIMARY KEY UNIQUE,
-            value TEXT
-        );
-        INSERT OR IGNORE INTO dyst (key, value) VALUES('version', '1');
-        ",
-    )?;
+    let created = !package_store.exists();
+    let mut connection = sqlite3::open(&package_store)?;
+    // other dyst processes only hold the database for short writes, see `migrations::migrate`
+    connection.set_busy_timeout(10_000)?;
+    migrations::migrate(&connection, &package_store, created)?;
 
     Ok(connection)
 }
+
+// held while a command modifies the package store; the operating system releases it once the
+// process exits, even after a crash
+pub struct PackageStoreLock {
+    _file: File,
+}
+
+pub fn lock_package_store() -> Result<PackageStoreLock> {
+    let mut lock_path = get_package_store()?;
+
+    if is_system_wide() {
+        check_system_wide_permissions(&lock_path)?;
+    }
+
+    create_dir_all(&lock_path)?;
+    lock_path.push("dyst.lock");
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(PackageStoreLock { _file: file }),
+        Err(TryLockError::WouldBlock) => Err(anyhow!(
+            "Another dyst is running, try again once it has finished (see {})",
+            lock_path.display()
+        )),
+        Err(TryLockError::Error(error)) => Err(error.into()),
+    }
+}
+
+// commands changing a system-wide installation are expected to be run as root
+fn check_system_wide_permissions(package_store: &Path) -> Result<()> {
+    for directory in [package_store.to_path_buf(), get_executables_path()?] {
+        // the package store is created on the first installation
+        let existing_directory = directory
+            .ancestors()
+            .find(|ancestor| ancestor.is_dir())
+            .unwrap_or(&directory);
+
+        if tempfile::tempfile_in(existing_directory).is_err() {
+            return Err(anyhow!(
+                "{} is not writable, system-wide installations have to be run as root (consider `sudo dyst --system ...`)",
+                existing_directory.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// rolled back unless committed, so that an error or a panic leaves no half-written rows; savepoints
+// are used instead of `BEGIN`, so that transactions can be nested
+pub struct Transaction<'a> {
+    connection: &'a sqlite3::Connection,
+    committed: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn begin(connection: &'a sqlite3::Connection) -> Result<Transaction<'a>> {
+        connection.execute("SAVEPOINT dyst")?;
+
+        Ok(Transaction {
+            connection,
+            committed: false,
+        })
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        self.connection.execute("RELEASE dyst")?;
+        self.committed = true;
+
+        Ok(())
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = self.connection.execute("ROLLBACK TO dyst; RELEASE dyst"); // ignore error
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_leading_out_of_the_profiles_directory_are_rejected() {
+        assert!(check_profile_name("ci").is_ok());
+        assert!(check_profile_name("work_2-tools").is_ok());
+
+        for name in ["../../x", "..", "a/b", "a\\b", "default", ""] {
+            assert!(check_profile_name(name).is_err(), "{}", name);
+            assert!(get_profile_path(name).is_err(), "{}", name);
+        }
+    }
+}
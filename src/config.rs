@@ -0,0 +1,320 @@
+use crate::common_directories;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+// looked for in the current directory and its parents
+pub const PROJECT_CONFIG_NAME: &str = ".dyst.toml";
+
+#[derive(Clone, Copy, PartialEq)]
+enum SettingKind {
+    Path,
+    Text,
+    Boolean,
+    Integer,
+}
+
+struct Setting {
+    key: &'static str,
+    kind: SettingKind,
+    // credentials, where requests are sent to, where packages and links are written and whether
+    // running a command may install anything are never taken from a checked out project
+    project: bool,
+}
+
+static SETTINGS: [Setting; 11] = [
+    Setting {
+        key: "binaries-path",
+        kind: SettingKind::Path,
+        project: false,
+    },
+    Setting {
+        key: "package-store",
+        kind: SettingKind::Path,
+        project: false,
+    },
+    Setting {
+        key: "github-token",
+        kind: SettingKind::Text,
+        project: false,
+    },
+    Setting {
+        key: "github-host",
+        kind: SettingKind::Text,
+        project: false,
+    },
+    Setting {
+        key: "prereleases",
+        kind: SettingKind::Boolean,
+        project: true,
+    },
+    Setting {
+        key: "parallelism",
+        kind: SettingKind::Integer,
+        project: true,
+    },
+    Setting {
+        key: "libc",
+        kind: SettingKind::Text,
+        project: true,
+    },
+    Setting {
+        key: "proxy",
+        kind: SettingKind::Text,
+        project: false,
+    },
+    Setting {
+        key: "cache-size",
+        kind: SettingKind::Integer,
+        project: false,
+    },
+    Setting {
+        key: "version-files",
+        kind: SettingKind::Boolean,
+        project: false,
+    },
+    Setting {
+        key: "install-pinned-versions",
+        kind: SettingKind::Boolean,
+        project: false,
+    },
+];
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Libc {
+    Gnu,
+    Musl,
+}
+
+impl Libc {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Libc::Gnu => "gnu",
+            Libc::Musl => "musl",
+        }
+    }
+}
+
+// a setting as read from one of the configuration files
+#[derive(Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: toml::Value,
+    pub source: PathBuf,
+}
+
+// environment variables take precedence over the configuration files, and flags over both
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub binaries_path: Option<PathBuf>,
+    pub package_store: Option<PathBuf>,
+    pub github_token: Option<String>,
+    pub github_host: Option<String>,
+    pub prereleases: Option<bool>,
+    pub parallelism: Option<usize>,
+    pub libc: Option<Libc>,
+    pub proxy: Option<String>,
+    // in bytes, downloads are only cached if this is set
+    pub cache_size: Option<u64>,
+    // links executables through launcher scripts honouring `.dyst-versions` files
+    pub version_files: Option<bool>,
+    // versions pinned in a `.dyst-versions` file are installed when they are first run
+    pub install_pinned_versions: Option<bool>,
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        Self::from_table(
+            Self::read_entries()?
+                .into_iter()
+                .map(|entry| (entry.key, entry.value)),
+        )
+    }
+
+    // the settings of the global and the project's configuration, the latter taking precedence
+    pub fn read_entries() -> Result<Vec<ConfigEntry>> {
+        let global_path = global_config_path()?;
+        let mut entries = read_table(&global_path)?
+            .into_iter()
+            .map(|(key, value)| ConfigEntry {
+                key,
+                value,
+                source: global_path.clone(),
+            })
+            .collect::<Vec<ConfigEntry>>();
+
+        if let Some(project_path) = find_project_config()? {
+            for (key, value) in read_table(&project_path)? {
+                let setting = find_setting(&key)?;
+
+                if !setting.project {
+                    return Err(anyhow!(
+                        "The setting `{}` in {} can only be set in the global configuration",
+                        key,
+                        project_path.display()
+                    ));
+                }
+
+                entries.retain(|entry| entry.key != key);
+                entries.push(ConfigEntry {
+                    key,
+                    value,
+                    source: project_path.clone(),
+                });
+            }
+        }
+
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Ok(entries)
+    }
+
+    fn from_table(entries: impl Iterator<Item = (String, toml::Value)>) -> Result<Config> {
+        toml::Value::Table(entries.collect())
+            .try_into()
+            .context("The configuration is invalid")
+    }
+
+    pub fn github_host(&self) -> String {
+        env::var("DYST_GITHUB_HOST")
+            .ok()
+            .or_else(|| self.github_host.clone())
+            .unwrap_or_else(|| String::from("github.com"))
+    }
+
+    // the token and host apply to every request to the GitHub API
+    pub fn initialise_github(&self) -> Result<()> {
+        let mut builder = octocrab::Octocrab::builder();
+
+        if let Some(token) = env::var("GITHUB_TOKEN")
+            .ok()
+            .or_else(|| self.github_token.clone())
+        {
+            builder = builder.personal_token(token);
+        }
+
+        // GitHub Enterprise serves its API below the host
+        let github_host = self.github_host();
+        if github_host != "github.com" {
+            builder = builder
+                .base_uri(format!("https://{}/api/v3", github_host))
+                .context("The configured GitHub host is invalid")?;
+        }
+
+        octocrab::initialise(builder.build()?);
+
+        Ok(())
+    }
+
+    // assets are downloaded through the configured proxy, unless one is set in the environment
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        let environment_proxy = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+            .iter()
+            .any(|variable| env::var_os(variable).is_some());
+
+        if let (Some(proxy), false) = (&self.proxy, environment_proxy) {
+            builder = builder
+                .proxy(reqwest::Proxy::all(proxy).context("The configured proxy is invalid")?);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+pub fn global_config_path() -> Result<PathBuf> {
+    common_directories::get_config_path()
+}
+
+// a project must not redirect a system-wide installation
+pub fn find_project_config() -> Result<Option<PathBuf>> {
+    if common_directories::is_system_wide() {
+        return Ok(None);
+    }
+
+    Ok(env::current_dir()?
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_CONFIG_NAME))
+        .find(|path| path.is_file()))
+}
+
+pub fn read_table(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+
+    toml::from_str(&read_to_string(path)?).context(format!(
+        "The configuration file {} is invalid",
+        path.display()
+    ))
+}
+
+// `None` removes the setting from the file
+pub fn write_setting(path: &Path, key: &str, value: Option<&str>, project: bool) -> Result<()> {
+    let setting = find_setting(key)?;
+
+    if project && !setting.project {
+        return Err(anyhow!(
+            "The setting `{}` can only be set in the global configuration",
+            key
+        ));
+    }
+
+    let mut table = read_table(path)?;
+
+    match value {
+        Some(value) => {
+            let value = match setting.kind {
+                SettingKind::Path | SettingKind::Text => toml::Value::String(value.to_string()),
+                SettingKind::Boolean => toml::Value::Boolean(
+                    value
+                        .parse()
+                        .context(format!("The setting `{}` expects `true` or `false`", key))?,
+                ),
+                SettingKind::Integer => toml::Value::Integer(
+                    value
+                        .parse()
+                        .context(format!("The setting `{}` expects a number", key))?,
+                ),
+            };
+
+            table.insert(key.to_string(), value);
+        }
+        None => {
+            table.remove(key);
+        }
+    }
+
+    // nothing invalid is ever written, e.g. an unknown libc
+    Config::from_table(table.clone().into_iter())?;
+
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    write(path, toml::to_string(&table)?)?;
+
+    Ok(())
+}
+
+pub fn check_key(key: &str) -> Result<()> {
+    find_setting(key).map(|_| ())
+}
+
+fn find_setting(key: &str) -> Result<&'static Setting> {
+    SETTINGS
+        .iter()
+        .find(|setting| setting.key == key)
+        .context(format!(
+            "The setting `{}` is unknown (expected one of {})",
+            key,
+            SETTINGS
+                .iter()
+                .map(|setting| setting.key)
+                .collect::<Vec<&str>>()
+                .join(", ")
+        ))
+}
@@ -0,0 +1,164 @@
+use crate::common_directories;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{copy, create_dir_all, read_dir, remove_dir, remove_dir_all, remove_file, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+// downloads are stored under the hash of their URL
+fn get_cache_path(source_url: &str) -> Result<PathBuf> {
+    let mut cache_path = common_directories::get_cache_path()?;
+    cache_path.push("downloads");
+    cache_path.push(format!("{:x}", Sha256::digest(source_url.as_bytes())));
+
+    Ok(cache_path)
+}
+
+pub fn lookup(source_url: &str) -> Result<Option<PathBuf>> {
+    let cache_path = get_cache_path(source_url)?;
+
+    Ok(Some(cache_path).filter(|path| path.is_file()))
+}
+
+// the oldest downloads are evicted once the cache grows past its size
+pub fn store(source_url: &str, file_path: &Path, cache_size: u64) -> Result<()> {
+    let cache_path = get_cache_path(source_url)?;
+    let cache_directory = cache_path
+        .parent()
+        .context("The download cache has no directory")?;
+
+    create_dir_all(cache_directory)?;
+    copy(file_path, &cache_path)?;
+
+    let mut cached_files = Vec::new();
+    for entry in read_dir(cache_directory)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        cached_files.push((metadata.modified()?, metadata.len(), entry.path()));
+    }
+
+    cached_files.sort();
+
+    let mut total_size = cached_files.iter().map(|file| file.1).sum::<u64>();
+    for (_, size, path) in cached_files {
+        if total_size <= cache_size {
+            break;
+        }
+
+        remove_file(&path)?;
+        total_size -= size;
+    }
+
+    Ok(())
+}
+
+// releases extracted for `dyst run` are evicted by when they were last run, the one that is about
+// to run is kept whatever the size of the cache
+pub fn evict_extracted_releases(release_path: &Path, cache_size: u64) -> Result<()> {
+    let mut run_cache_path = common_directories::get_cache_path()?;
+    run_cache_path.push("run");
+
+    evict_releases(&run_cache_path, release_path, cache_size)
+}
+
+fn evict_releases(run_cache_path: &Path, release_path: &Path, cache_size: u64) -> Result<()> {
+    File::open(release_path)?.set_modified(SystemTime::now())?;
+
+    // releases are extracted to `run/author/name/tag/asset`, next to hidden temporary directories
+    let mut cached_releases = Vec::new();
+    for entry in WalkDir::new(run_cache_path)
+        .min_depth(4)
+        .max_depth(4)
+        .into_iter()
+        .filter_entry(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+    {
+        let entry = entry?;
+
+        if entry.path() == release_path || !entry.file_type().is_dir() {
+            continue;
+        }
+
+        cached_releases.push((
+            entry.metadata()?.modified()?,
+            get_directory_size(entry.path()),
+            entry.into_path(),
+        ));
+    }
+
+    cached_releases.sort();
+
+    let mut total_size = get_directory_size(release_path)
+        + cached_releases.iter().map(|release| release.1).sum::<u64>();
+    for (_, size, path) in cached_releases {
+        if total_size <= cache_size {
+            break;
+        }
+
+        remove_dir_all(&path)?;
+        total_size -= size;
+
+        // the tag, name and author directories are dropped once they are empty
+        for parent_path in path.ancestors().skip(1) {
+            if parent_path == run_cache_path || remove_dir(parent_path).is_err() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get_directory_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use std::time::Duration;
+
+    fn extract_release(run_cache_path: &Path, release: &str, size: usize, age: u64) -> PathBuf {
+        let release_path = run_cache_path.join(release);
+        create_dir_all(&release_path).unwrap();
+        write(release_path.join("tool"), vec![0u8; size]).unwrap();
+
+        File::open(&release_path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+
+        release_path
+    }
+
+    #[test]
+    fn least_recently_run_releases_are_evicted() {
+        let directory = tempfile::tempdir().unwrap();
+        let run_cache_path = directory.path();
+
+        let oldest = extract_release(run_cache_path, "author/old/v1/tool.tar.gz", 100, 300);
+        let older = extract_release(run_cache_path, "author/name/v1/tool.tar.gz", 100, 200);
+        let recent = extract_release(run_cache_path, "author/name/v2/tool.tar.gz", 100, 100);
+        let running = extract_release(run_cache_path, "author/name/v3/tool.tar.gz", 100, 400);
+        create_dir_all(run_cache_path.join("author/name/.tmp123/tool.tar.gz")).unwrap();
+
+        evict_releases(run_cache_path, &running, 250).unwrap();
+
+        assert!(!oldest.exists() && !older.exists());
+        assert!(!run_cache_path.join("author/old").exists());
+        assert!(recent.is_dir() && running.is_dir());
+        assert!(run_cache_path.join("author/name/.tmp123").is_dir());
+
+        // the release about to run stays, even if it exceeds the cache on its own
+        evict_releases(run_cache_path, &running, 0).unwrap();
+        assert!(!recent.exists() && running.is_dir());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::extraction_filter::ExtractionFilter;
+use crate::{appimage, archive, common_directories};
+use anyhow::Result;
+use file_format::{FileFormat, Kind};
+use glob::Pattern;
+use std::fs::{metadata, File};
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+#[derive(Clone, Default)]
+pub struct ExecutableDetector {
+    pub executable_patterns: Vec<Pattern>,
+    pub non_executable_patterns: Vec<Pattern>,
+}
+
+impl ExecutableDetector {
+    pub fn from_index_db(
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<ExecutableDetector> {
+        let mut statement = index_db.prepare(
+            "SELECT executableGlobs, nonExecutableGlobs FROM packages WHERE repository = ?",
+        )?;
+        statement.bind(
+            1,
+            format!("{}/{}", repository_author, repository_name).as_str(),
+        )?;
+
+        if statement.next()? != sqlite3::State::Row {
+            return Ok(ExecutableDetector::default());
+        }
+
+        Ok(ExecutableDetector {
+            executable_patterns: ExtractionFilter::deserialize_patterns(
+                statement.read::<String>(0).ok(),
+            )?,
+            non_executable_patterns: ExtractionFilter::deserialize_patterns(
+                statement.read::<String>(1).ok(),
+            )?,
+        })
+    }
+
+    // shared by install, rename and list-execs, so that they always agree on what gets linked
+    pub fn find_executables(
+        &self,
+        package_path: &Path,
+        extraction_filter: &ExtractionFilter,
+    ) -> Result<Vec<PathBuf>> {
+        let mut executables = Vec::new();
+
+        for entry in WalkDir::new(common_directories::get_executable_search_path(package_path))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| Self::is_contained_file(package_path, entry))
+        {
+            let relative_path = entry.path().strip_prefix(package_path)?;
+
+            if extraction_filter.is_selected(relative_path)
+                && self.is_executable(entry.path(), relative_path)?
+            {
+                executables.push(entry.path().to_path_buf());
+            }
+        }
+
+        Ok(executables)
+    }
+
+    // archives often link a versioned executable into `bin`, which is followed as long as it stays
+    // within the package
+    fn is_contained_file(package_path: &Path, entry: &DirEntry) -> bool {
+        if entry.file_type().is_symlink() {
+            return archive::resolves_within(package_path, entry.path())
+                && metadata(entry.path()).is_ok_and(|metadata| metadata.is_file());
+        }
+
+        entry.file_type().is_file()
+    }
+
+    pub fn is_executable(&self, path: &Path, relative_path: &Path) -> Result<bool> {
+        if Self::matches_any(&self.non_executable_patterns, relative_path) {
+            return Ok(false);
+        }
+
+        if Self::matches_any(&self.executable_patterns, relative_path) {
+            return Ok(true);
+        }
+
+        // shared libraries are ELF files just like executables, but must not be linked
+        if Self::is_shared_library(relative_path) {
+            return Ok(false);
+        }
+
+        if FileFormat::from_file(path)?.kind() == Kind::Executable || Self::has_shebang(path)? {
+            return Ok(true);
+        }
+
+        Ok(metadata(path)?.permissions().mode() & 0o111 != 0)
+    }
+
+    // patterns match either the path relative to the package directory or just the file name
+    fn matches_any(patterns: &[Pattern], relative_path: &Path) -> bool {
+        let file_name = relative_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(relative_path) || pattern.matches(&file_name))
+    }
+
+    fn is_shared_library(relative_path: &Path) -> bool {
+        let file_name = relative_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        file_name.ends_with(".so")
+            || file_name.contains(".so.")
+            || file_name.ends_with(".dylib")
+            || file_name.ends_with(".dll")
+    }
+
+    fn has_shebang(path: &Path) -> Result<bool> {
+        let mut magic = [0u8; 2];
+
+        match File::open(path)?.read_exact(&mut magic) {
+            Ok(_) => Ok(&magic == b"#!"),
+            Err(_) => Ok(false),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct LinkSelection {
+    // pairs of an executable's file name and the name to link it under
+    pub rules: Vec<(String, String)>,
+    // only link executables with a rule (`--bin`) instead of all of them (`--rename`)
+    pub exclusive: bool,
+}
+
+impl LinkSelection {
+    pub fn from_index_db(
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<LinkSelection> {
+        let repository = format!("{}/{}", repository_author, repository_name);
+        let mut selection = LinkSelection::default();
+
+        let mut statement = index_db
+            .prepare("SELECT exclusiveLinks, execRename FROM packages WHERE repository = ?")?;
+        statement.bind(1, repository.as_str())?;
+
+        if statement.next()? == sqlite3::State::Row {
+            selection.exclusive = statement.read::<i64>(0)? != 0;
+
+            // packages installed before `executableLinks` existed stored a single `match/replace` rule
+            if let Ok(exec_rename) = statement.read::<String>(1) {
+                if let Some((executable, link_name)) = exec_rename.split_once('/') {
+                    selection.rename(executable, link_name);
+                }
+            }
+        }
+
+        let mut statement = index_db.prepare(
+            "SELECT executable, linkName FROM executableLinks WHERE repository = ? ORDER BY rowid",
+        )?;
+        statement.bind(1, repository.as_str())?;
+
+        while let sqlite3::State::Row = statement.next()? {
+            selection
+                .rules
+                .push((statement.read::<String>(0)?, statement.read::<String>(1)?));
+        }
+
+        Ok(selection)
+    }
+
+    pub fn save(
+        &self,
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<()> {
+        // `exclusiveLinks` is part of the package's entry itself
+        let repository = format!("{}/{}", repository_author, repository_name);
+        Self::delete(index_db, &repository)?;
+
+        for (executable, link_name) in &self.rules {
+            let mut statement = index_db.prepare(
+                "INSERT INTO executableLinks (repository, executable, linkName) VALUES(?, ?, ?)",
+            )?;
+            statement.bind(1, repository.as_str())?;
+            statement.bind(2, executable.as_str())?;
+            statement.bind(3, link_name.as_str())?;
+            while statement.next()? != sqlite3::State::Done {}
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(index_db: &sqlite3::Connection, repository: &str) -> Result<()> {
+        let mut statement = index_db.prepare("DELETE FROM executableLinks WHERE repository = ?")?;
+        statement.bind(1, repository)?;
+        while statement.next()? != sqlite3::State::Done {}
+
+        Ok(())
+    }
+
+    // replaces all links of an executable with a single one
+    pub fn rename(&mut self, executable: &str, link_name: &str) {
+        self.rules.retain(|rule| rule.0 != executable);
+        self.rules
+            .push((executable.to_string(), link_name.to_string()));
+    }
+
+    // an empty result means that the executable is ignored
+    pub fn link_names(&self, executable_path: &Path, repository_name: &str) -> Result<Vec<String>> {
+        let file_name = executable_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let link_names = self
+            .rules
+            .iter()
+            .filter(|rule| rule.0 == file_name)
+            .map(|rule| rule.1.clone())
+            .collect::<Vec<String>>();
+
+        if !link_names.is_empty() || self.exclusive {
+            return Ok(link_names);
+        }
+
+        // AppImages are usually named `Tool-1.2.3-x86_64.AppImage`
+        if appimage::is_appimage(executable_path)? {
+            return Ok(vec![repository_name.to_string()]);
+        }
+
+        Ok(vec![file_name.to_string()])
+    }
+
+    // the executable of another version which is linked under a name of `executable_path`, as
+    // their paths can differ in a versioned top-level directory
+    pub fn find_counterpart(
+        &self,
+        executables: &[PathBuf],
+        executable_path: &Path,
+        repository_name: &str,
+    ) -> Result<Option<PathBuf>> {
+        let link_names = self.link_names(executable_path, repository_name)?;
+
+        for path in executables {
+            if self
+                .link_names(path, repository_name)?
+                .iter()
+                .any(|link_name| link_names.contains(link_name))
+            {
+                return Ok(Some(path.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir, write};
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn symlinked_executables_within_the_package_are_found() {
+        let package_directory = tempfile::tempdir().unwrap();
+        let package_path = package_directory.path();
+        create_dir(package_path.join("bin")).unwrap();
+        create_dir(package_path.join("libexec")).unwrap();
+        write(package_path.join("libexec/tool-1.2"), "#!/bin/sh\n").unwrap();
+        symlink("../libexec/tool-1.2", package_path.join("bin/tool")).unwrap();
+        symlink("/bin/sh", package_path.join("bin/shell")).unwrap();
+        symlink("../libexec", package_path.join("bin/directory")).unwrap();
+
+        let mut executables = ExecutableDetector::default()
+            .find_executables(package_path, &ExtractionFilter::default())
+            .unwrap();
+        executables.sort();
+
+        assert_eq!(
+            executables,
+            vec![
+                package_path.join("bin/tool"),
+                package_path.join("libexec/tool-1.2")
+            ]
+        );
+    }
+
+    #[test]
+    fn counterparts_in_other_versions_are_found_by_link_name() {
+        let package_directory = tempfile::tempdir().unwrap();
+        let package_path = package_directory.path();
+        create_dir(package_path.join("tool-1.2")).unwrap();
+        create_dir(package_path.join("tool-1.3")).unwrap();
+        write(package_path.join("tool-1.2/tool"), "#!/bin/sh\n").unwrap();
+        write(package_path.join("tool-1.3/tool"), "#!/bin/sh\n").unwrap();
+        write(package_path.join("tool-1.3/helper"), "#!/bin/sh\n").unwrap();
+
+        let executables = vec![
+            package_path.join("tool-1.3/helper"),
+            package_path.join("tool-1.3/tool"),
+        ];
+        let link_selection = LinkSelection::default();
+
+        assert_eq!(
+            link_selection
+                .find_counterpart(&executables, &package_path.join("tool-1.2/tool"), "tool")
+                .unwrap(),
+            Some(package_path.join("tool-1.3/tool"))
+        );
+        assert_eq!(
+            link_selection
+                .find_counterpart(
+                    &executables[..1],
+                    &package_path.join("tool-1.2/tool"),
+                    "tool"
+                )
+                .unwrap(),
+            None
+        );
+    }
+}
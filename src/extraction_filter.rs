@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use glob::Pattern;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Clone, Default)]
+pub struct ExtractionFilter {
+    pub strip_components: usize,
+    pub include_patterns: Vec<Pattern>,
+    pub exclude_patterns: Vec<Pattern>,
+}
+
+impl ExtractionFilter {
+    pub fn parse_patterns(patterns: &[String]) -> Result<Vec<Pattern>> {
+        patterns
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).context(format!(
+                    "The glob pattern `{}` contains illegal syntax",
+                    pattern
+                ))
+            })
+            .collect()
+    }
+
+    pub fn from_index_db(
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<ExtractionFilter> {
+        let mut statement = index_db.prepare(
+            "SELECT stripComponents, includeGlobs, excludeGlobs FROM packages WHERE repository = ?",
+        )?;
+        statement.bind(
+            1,
+            format!("{}/{}", repository_author, repository_name).as_str(),
+        )?;
+
+        if statement.next()? != sqlite3::State::Row {
+            return Ok(ExtractionFilter::default());
+        }
+
+        Ok(ExtractionFilter {
+            strip_components: statement.read::<i64>(0)? as usize,
+            include_patterns: Self::deserialize_patterns(statement.read::<String>(1).ok())?,
+            exclude_patterns: Self::deserialize_patterns(statement.read::<String>(2).ok())?,
+        })
+    }
+
+    // patterns are stored newline-separated in the index database
+    pub fn serialize_patterns(patterns: &[Pattern]) -> Option<String> {
+        if patterns.is_empty() {
+            return None;
+        }
+
+        Some(
+            patterns
+                .iter()
+                .map(Pattern::as_str)
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        )
+    }
+
+    pub fn deserialize_patterns(patterns: Option<String>) -> Result<Vec<Pattern>> {
+        match patterns {
+            Some(patterns) => Self::parse_patterns(
+                &patterns
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<String>>(),
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // maps a sanitized archive entry onto its path relative to the package directory, if it is selected
+    pub fn apply(&self, entry_path: &Path) -> Option<PathBuf> {
+        let stripped_path = entry_path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .skip(self.strip_components)
+            .collect::<PathBuf>();
+
+        if stripped_path.as_os_str().is_empty() || !self.is_selected(&stripped_path) {
+            return None;
+        }
+
+        Some(stripped_path)
+    }
+
+    pub fn is_selected(&self, relative_path: &Path) -> bool {
+        let included = self.include_patterns.is_empty()
+            || self
+                .include_patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(relative_path));
+        let excluded = self
+            .exclude_patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(relative_path));
+
+        included && !excluded
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use std::env;
+
+// a change to an installed package, recorded in the append-only `history` table
+#[derive(Default)]
+pub struct HistoryEvent<'a> {
+    // one of install, update, remove, lock, unlock, rename and use
+    pub action: &'a str,
+    pub old_tag: Option<&'a str>,
+    pub new_tag: Option<&'a str>,
+    pub asset_name: Option<&'a str>,
+    pub checksum: Option<&'a str>,
+    pub details: Option<&'a str>,
+}
+
+impl HistoryEvent<'_> {
+    pub fn record(&self, index_db: &sqlite3::Connection, repository: &str) -> Result<()> {
+        let mut statement = index_db.prepare("INSERT INTO history (timestamp, repository, action, oldTag, newTag, assetName, checksum, details, user, commandLine) VALUES(datetime('now'), ?, ?, ?, ?, ?, ?, ?, ?, ?)")?;
+        statement.bind(1, repository)?;
+        statement.bind(2, self.action)?;
+
+        let user = Self::current_user();
+        let optional_values = [
+            self.old_tag,
+            self.new_tag,
+            self.asset_name,
+            self.checksum,
+            self.details,
+            user.as_deref(),
+        ];
+
+        for (index, value) in optional_values.into_iter().enumerate() {
+            match value {
+                Some(value) => statement.bind(index + 3, value)?,
+                None => statement.bind(index + 3, &sqlite3::Value::Null)?,
+            };
+        }
+
+        statement.bind(9, env::args().collect::<Vec<String>>().join(" ").as_str())?;
+
+        while statement.next()? != sqlite3::State::Done {}
+
+        Ok(())
+    }
+
+    fn current_user() -> Option<String> {
+        env::var("USER").or_else(|_| env::var("LOGNAME")).ok()
+    }
+}
+
+// whether dyst has ever installed the repository
+pub fn has_history(index_db: &sqlite3::Connection, repository: &str) -> Result<bool> {
+    let mut statement = index_db.prepare("SELECT 1 FROM history WHERE repository = ? LIMIT 1")?;
+    statement.bind(1, repository)?;
+
+    Ok(statement.next()? == sqlite3::State::Row)
+}
@@ -0,0 +1,471 @@
+use crate::config::Config;
+use crate::shims::Shim;
+use crate::{common_directories, manifest};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs::{
+    create_dir_all, read, read_dir, read_link, remove_file, set_permissions, write, Permissions,
+};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use symlink::symlink_file;
+
+// a link as it is on disk, so that it can be put back exactly as it was; files that are forcibly
+// replaced are kept with their contents and mode
+pub enum SavedLink {
+    Symlink(PathBuf),
+    File(Vec<u8>, u32),
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ConflictPolicy {
+    #[default]
+    Abort,
+    Force,
+    Skip,
+    Alias,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Abort => "abort",
+            ConflictPolicy::Force => "force",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Alias => "alias",
+        }
+    }
+
+    // replacing other packages' links is only ever done for the installation it was passed to, so
+    // that updates, renames and `dyst use` never take over links on their own
+    pub fn persisted(&self) -> ConflictPolicy {
+        match self {
+            ConflictPolicy::Force => ConflictPolicy::Abort,
+            policy => *policy,
+        }
+    }
+
+    pub fn from_index_db(
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<ConflictPolicy> {
+        let mut statement =
+            index_db.prepare("SELECT linkConflicts FROM packages WHERE repository = ?")?;
+        statement.bind(
+            1,
+            format!("{}/{}", repository_author, repository_name).as_str(),
+        )?;
+
+        if statement.next()? != sqlite3::State::Row {
+            return Ok(ConflictPolicy::default());
+        }
+
+        Ok(match statement.read::<String>(0)?.as_str() {
+            "skip" => ConflictPolicy::Skip,
+            "alias" => ConflictPolicy::Alias,
+            _ => ConflictPolicy::Abort,
+        })
+    }
+}
+
+pub fn find_link_owner(index_db: &sqlite3::Connection, link_path: &Path) -> Result<Option<String>> {
+    let mut statement = index_db.prepare("SELECT repository FROM files WHERE path = ?")?;
+    statement.bind(1, path_to_str(link_path)?)?;
+
+    if statement.next()? != sqlite3::State::Row {
+        return Ok(None);
+    }
+
+    Ok(Some(statement.read::<String>(0)?))
+}
+
+// checks all links of a package before any of them is created or replaced; `repository` owns the
+// links, which is `author/name@tag` for additional versions, and whatever is replaced is added to
+// `replaced_links` for `restore_links`, in case the caller fails afterwards
+pub fn resolve_conflicts(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    repository_author: &str,
+    package_path: &Path,
+    planned_links: Vec<(PathBuf, String)>,
+    policy: ConflictPolicy,
+    replaced_links: &mut Vec<(PathBuf, SavedLink)>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let executables_path = common_directories::get_executables_path()?;
+    let mut resolved_links = Vec::new();
+    let mut replaced_paths = Vec::new();
+    let mut linked_names = HashSet::new();
+    let mut conflicts = Vec::new();
+
+    for (target, link_name) in planned_links {
+        if !linked_names.insert(link_name.clone()) {
+            conflicts.push(format!(
+                "'{}' is linked for more than one executable of the package",
+                link_name
+            ));
+            continue;
+        }
+
+        let mut link_path = executables_path.clone();
+        link_path.push(&link_name);
+
+        let owner = find_link_owner(index_db, &link_path)?;
+
+        // a package's own links are replaced anyway
+        if link_path.symlink_metadata().is_err() || owner.as_deref() == Some(repository) {
+            resolved_links.push((target, link_path));
+            continue;
+        }
+
+        // packages installed before links were tracked only have the targets of their links to go by
+        if owner.is_none()
+            && read_link(&link_path).is_ok_and(|linked_path| linked_path.starts_with(package_path))
+        {
+            replaced_paths.push(link_path.clone());
+            resolved_links.push((target, link_path));
+            continue;
+        }
+
+        let reason = match &owner {
+            Some(owner) => format!("is provided by '{}'", owner),
+            None => String::from("already exists and is not managed by dyst"),
+        };
+
+        match policy {
+            ConflictPolicy::Abort => conflicts.push(format!("'{}' {}", link_name, reason)),
+            ConflictPolicy::Force => {
+                println!("  Warning: Replacing '{}', which {}", link_name, reason);
+
+                if link_path.is_dir() && !link_path.is_symlink() {
+                    conflicts.push(format!(
+                        "'{}' is a directory and cannot be replaced",
+                        link_path.display()
+                    ));
+                    continue;
+                }
+
+                replaced_paths.push(link_path.clone());
+                resolved_links.push((target, link_path));
+            }
+            ConflictPolicy::Skip => {
+                println!("  Warning: Skipping '{}', which {}", link_name, reason);
+            }
+            ConflictPolicy::Alias => {
+                let alias = format!("{}-{}", repository_author, link_name);
+                let mut alias_path = executables_path.clone();
+                alias_path.push(&alias);
+
+                if alias_path.symlink_metadata().is_ok() || !linked_names.insert(alias.clone()) {
+                    conflicts.push(format!(
+                        "'{}' {} and the alias '{}' exists as well",
+                        link_name, reason, alias
+                    ));
+                    continue;
+                }
+
+                println!(
+                    "  Warning: Linking '{}' as '{}', as it {}",
+                    link_name, alias, reason
+                );
+                resolved_links.push((target, alias_path));
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        return Err(anyhow!(
+            "The executables conflict with existing files (consider passing `--force`, `--skip-conflicts` or `--alias-conflicts`):\n  {}",
+            conflicts.join("\n  ")
+        ));
+    }
+
+    for link_path in replaced_paths {
+        if let Some(saved_link) = save_link(&link_path)? {
+            replaced_links.push((link_path.clone(), saved_link));
+        }
+
+        remove_file(&link_path)?;
+        forget_link(index_db, &link_path)?;
+    }
+
+    Ok(resolved_links)
+}
+
+pub fn create_link(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    target: &Path,
+    link_path: &Path,
+    shim: &Shim,
+) -> Result<()> {
+    // additional versions are always run as they are, whatever is pinned
+    let version_files = !repository.contains('@') && Config::load()?.version_files.unwrap_or(false);
+
+    if shim.is_enabled() || version_files {
+        shim.write(repository, target, link_path, version_files)?;
+    } else {
+        symlink_file(target, link_path)?;
+    }
+
+    track_link(index_db, repository, target, link_path, "executable")
+}
+
+// man pages and shell completions are linked outside of the executables path
+pub fn create_shared_link(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    target: &Path,
+    link_path: &Path,
+) -> Result<()> {
+    if let Some(parent) = link_path.parent() {
+        create_dir_all(parent)?;
+    }
+    symlink_file(target, link_path)?;
+
+    track_link(index_db, repository, target, link_path, "shared")
+}
+
+pub fn remove_links(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    package_path: &Path,
+) -> Result<()> {
+    remove_tracked_links(
+        index_db,
+        "SELECT path FROM files WHERE repository = ? AND kind != 'file'",
+        repository,
+        package_path,
+    )
+}
+
+// man pages and shell completions stay in place when only the executables are relinked
+pub fn remove_executable_links(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    package_path: &Path,
+) -> Result<()> {
+    remove_tracked_links(
+        index_db,
+        "SELECT path FROM files WHERE repository = ? AND kind = 'executable'",
+        repository,
+        package_path,
+    )
+}
+
+// the links of a package that is about to be replaced, see `restore_links`
+pub fn save_links(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    package_path: &Path,
+) -> Result<Vec<(PathBuf, SavedLink)>> {
+    let mut link_paths = Vec::new();
+    let mut statement =
+        index_db.prepare("SELECT path FROM files WHERE repository = ? AND kind != 'file'")?;
+    statement.bind(1, repository)?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        link_paths.push(PathBuf::from(statement.read::<String>(0)?));
+    }
+
+    if link_paths.is_empty() && !manifest::is_recorded(index_db, repository)? {
+        link_paths = find_untracked_links(package_path)?;
+    }
+
+    let mut saved_links = Vec::new();
+
+    for link_path in link_paths {
+        if let Some(saved_link) = save_link(&link_path)? {
+            saved_links.push((link_path, saved_link));
+        }
+    }
+
+    Ok(saved_links)
+}
+
+fn save_link(link_path: &Path) -> Result<Option<SavedLink>> {
+    if let Ok(target) = read_link(link_path) {
+        return Ok(Some(SavedLink::Symlink(target)));
+    }
+
+    match link_path.metadata() {
+        Ok(metadata) if metadata.is_file() => Ok(Some(SavedLink::File(
+            read(link_path)?,
+            metadata.permissions().mode(),
+        ))),
+        _ => Ok(None),
+    }
+}
+
+// recreates links saved by `save_links` or replaced by `resolve_conflicts`; the database rows are
+// expected to be rolled back already
+pub fn restore_links(saved_links: &[(PathBuf, SavedLink)]) -> Result<()> {
+    for (link_path, saved_link) in saved_links {
+        if link_path.is_symlink() || Shim::is_shim(link_path) {
+            remove_file(link_path)?;
+        } else if link_path.symlink_metadata().is_ok() {
+            // whatever has taken its place since is left alone
+            continue;
+        }
+
+        if let Some(parent) = link_path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        match saved_link {
+            SavedLink::Symlink(target) => symlink_file(target, link_path)?,
+            SavedLink::File(contents, mode) => {
+                write(link_path, contents)?;
+                set_permissions(link_path, Permissions::from_mode(*mode))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn track_link(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    target: &Path,
+    link_path: &Path,
+    kind: &str,
+) -> Result<()> {
+    let mut statement = index_db.prepare(
+        "INSERT OR REPLACE INTO files (path, repository, target, kind) VALUES(?, ?, ?, ?)",
+    )?;
+    statement.bind(1, path_to_str(link_path)?)?;
+    statement.bind(2, repository)?;
+    statement.bind(3, path_to_str(target)?)?;
+    statement.bind(4, kind)?;
+    while statement.next()? != sqlite3::State::Done {}
+
+    Ok(())
+}
+
+fn remove_tracked_links(
+    index_db: &sqlite3::Connection,
+    query: &str,
+    repository: &str,
+    package_path: &Path,
+) -> Result<()> {
+    let mut link_paths = Vec::new();
+    let mut statement = index_db.prepare(query)?;
+    statement.bind(1, repository)?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        link_paths.push(PathBuf::from(statement.read::<String>(0)?));
+    }
+
+    if link_paths.is_empty() && !manifest::is_recorded(index_db, repository)? {
+        return remove_untracked_links(package_path);
+    }
+
+    for link_path in link_paths {
+        // only remove what is still a symlink or shim, in case it has been replaced in the meantime
+        if link_path.is_symlink() || Shim::is_shim(&link_path) {
+            remove_file(&link_path)?;
+        }
+
+        forget_link(index_db, &link_path)?;
+    }
+
+    Ok(())
+}
+
+// packages installed before links were tracked only have the targets of their links to go by
+fn remove_untracked_links(package_path: &Path) -> Result<()> {
+    for link_path in find_untracked_links(package_path)? {
+        remove_file(&link_path)?;
+    }
+
+    Ok(())
+}
+
+fn find_untracked_links(package_path: &Path) -> Result<Vec<PathBuf>> {
+    let executables_path = common_directories::get_executables_path()?;
+    let mut link_paths = Vec::new();
+
+    if !executables_path.is_dir() {
+        return Ok(link_paths);
+    }
+
+    for entry in read_dir(executables_path)? {
+        let path = entry?.path();
+
+        // anything unreadable can't be one of the package's links
+        if let Ok(linked_path) = read_link(&path) {
+            if linked_path.starts_with(package_path) {
+                link_paths.push(path);
+            }
+        }
+    }
+
+    Ok(link_paths)
+}
+
+pub fn forget_link(index_db: &sqlite3::Connection, link_path: &Path) -> Result<()> {
+    let mut statement = index_db.prepare("DELETE FROM files WHERE path = ?")?;
+    statement.bind(1, path_to_str(link_path)?)?;
+    while statement.next()? != sqlite3::State::Done {}
+
+    Ok(())
+}
+
+// paths are stored as text, so they have to be valid UTF-8
+pub fn path_to_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("The path {} is not valid UTF-8", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn saved_links_are_restored() {
+        let directory = tempfile::tempdir().unwrap();
+        let package_path = directory.path().join("package");
+        let symlink_path = directory.path().join("tool");
+        let shim_path = directory.path().join("tool-shim");
+        let script = "#!/bin/sh\n# generated by dyst\nexec tool \"$@\"\n";
+
+        create_dir_all(&package_path).unwrap();
+        symlink_file(package_path.join("tool"), &symlink_path).unwrap();
+        write(&shim_path, script).unwrap();
+
+        let index_db = sqlite3::open(":memory:").unwrap();
+        index_db
+            .execute(
+                "CREATE TABLE files (path TEXT PRIMARY KEY UNIQUE, repository TEXT NOT NULL, kind TEXT NOT NULL, target TEXT)",
+            )
+            .unwrap();
+        for link_path in [&symlink_path, &shim_path] {
+            track_link(
+                &index_db,
+                "author/name",
+                &package_path.join("tool"),
+                link_path,
+                "executable",
+            )
+            .unwrap();
+        }
+
+        let saved_links = save_links(&index_db, "author/name", &package_path).unwrap();
+        assert_eq!(saved_links.len(), 2);
+
+        remove_file(&symlink_path).unwrap();
+        remove_file(&shim_path).unwrap();
+        restore_links(&saved_links).unwrap();
+
+        assert_eq!(read_link(&symlink_path).unwrap(), package_path.join("tool"));
+        assert_eq!(read_to_string(&shim_path).unwrap(), script);
+
+        // whatever has taken the place of a link since is left alone
+        remove_file(&symlink_path).unwrap();
+        write(&symlink_path, "replaced").unwrap();
+        restore_links(&saved_links).unwrap();
+        assert_eq!(read_to_string(&symlink_path).unwrap(), "replaced");
+    }
+}
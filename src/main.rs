@@ -1,18 +1,53 @@
 use anyhow::{anyhow, Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use config::Config;
+use extraction_filter::ExtractionFilter;
 use itertools::Itertools;
+use links::ConflictPolicy;
+use output::OutputFormat;
 use regex::Regex;
+use std::path::PathBuf;
 
+mod appimage;
+mod archive;
 mod cli;
 mod common_directories;
+mod config;
+mod download_cache;
+mod executables;
+mod extraction_filter;
+mod history;
+mod links;
+mod manifest;
+mod migrations;
+mod output;
+mod shared_files;
+mod shims;
+mod version_files;
+mod versions;
 
 #[derive(Parser)]
 #[command(author, version, about)]
 struct ArgumentParser {
     #[command(subcommand)]
     command: Commands,
+
+    /// The format of listings and package details
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+
+    /// Manage the system-wide installation (in /opt/dyst and /usr/local/bin by default)
+    #[arg(long, global = true)]
+    system: bool,
+
+    /// Use a separate set of packages, overriding `DYST_PROFILE` (see `dyst profile`)
+    #[arg(long, global = true)]
+    profile: Option<String>,
 }
 
+// the arguments are only parsed once, so the size of the install variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Install an asset from a GitHub repository
@@ -20,7 +55,7 @@ enum Commands {
         /// The repository to install from (ex. DISTREAT/projavu)
         repository: String,
 
-        /// Specify a tag to install
+        /// Specify a tag to install, next to the installed version if the repository is installed
         #[arg(short, long)]
         tag: Option<String>,
 
@@ -28,13 +63,21 @@ enum Commands {
         #[arg(short, long)]
         prerelease: bool,
 
+        /// Never download prereleases, even if the configuration allows them
+        #[arg(long, overrides_with = "prerelease")]
+        no_prerelease: bool,
+
         /// Select a specific asset by applying a custom regex filter on the asset name
         #[arg(short, long)]
         filter: Option<String>,
 
         /// Replace the executable's name (ex. `binary-xyz/binary` to replace `binary-xyz` with `binary`)
         #[arg(short, long)]
-        rename: Option<String>,
+        rename: Vec<String>,
+
+        /// Only link the selected executables, optionally under an alias (ex. `binary-xyz=binary`)
+        #[arg(short, long, value_name = "NAME[=ALIAS]")]
+        bin: Vec<String>,
 
         /// Lock the package, preventing updates
         #[arg(short, long)]
@@ -44,11 +87,96 @@ enum Commands {
         /// List all assets for the selected release
         #[arg(short, long)]
         assets: bool,
+
+        /// Install the desktop entry and icon embedded in AppImages
+        #[arg(short, long)]
+        desktop: bool,
+
+        /// Strip the given number of leading path components from archive entries
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        strip_components: usize,
+
+        /// Only extract and link files matching a glob pattern (ex. `bin/*`)
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Skip files matching a glob pattern during extraction and linking
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Always treat files matching a glob pattern as executables
+        #[arg(long, value_name = "GLOB")]
+        executable: Vec<String>,
+
+        /// Never treat files matching a glob pattern as executables (ex. `*.so`)
+        #[arg(long, value_name = "GLOB")]
+        not_executable: Vec<String>,
+
+        /// Replace existing files and links of other packages with the executables' links
+        #[arg(long, group = "conflicts")]
+        force: bool,
+
+        /// Do not link executables whose names are already taken
+        #[arg(long, group = "conflicts")]
+        skip_conflicts: bool,
+
+        /// Link executables whose names are already taken as `author-name` instead
+        #[arg(long, group = "conflicts")]
+        alias_conflicts: bool,
+
+        /// Set an environment variable for the executables, linking them through a launcher script
+        /// (ex. `LD_LIBRARY_PATH=$DYST_PACKAGE/lib`)
+        #[arg(short, long, value_name = "KEY=VALUE")]
+        env: Vec<String>,
+
+        /// Pass a fixed argument to the executables, linking them through a launcher script
+        #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+        arg: Vec<String>,
     },
     /// Remove an installed asset
     Remove {
-        /// The repository to uninstall (ex. DISTREAT/projavu)
+        /// The repository to uninstall (ex. DISTREAT/projavu), or one of its versions (ex. DISTREAT/projavu@v1.0)
+        repository: String,
+    },
+    /// Download a release into the cache and run one of its executables, without installing it
+    #[command(visible_alias = "exec")]
+    Run {
+        /// The repository to run (ex. BurntSushi/ripgrep), optionally with a tag (ex. BurntSushi/ripgrep@14.0.0)
+        repository: String,
+
+        /// Allow the download of prereleases
+        #[arg(short, long)]
+        prerelease: bool,
+
+        /// Select a specific asset by applying a custom regex filter on the asset name
+        #[arg(short, long)]
+        filter: Option<String>,
+
+        /// The executable to run, if the release contains several
+        #[arg(short, long, value_name = "NAME")]
+        bin: Option<String>,
+
+        /// Install the release and link its executables, as `dyst install` would
+        #[arg(short, long)]
+        keep: bool,
+
+        /// The arguments passed to the executable
+        #[arg(last = true)]
+        arguments: Vec<String>,
+    },
+    /// Print the directory of the version pinned for the current directory in `.dyst-versions`
+    Resolve {
+        /// The repository in question
+        repository: String,
+        /// An executable of the default version, relative to its directory, to find in the pinned one
+        executable: Option<PathBuf>,
+    },
+    /// Link another installed version of a repository under the plain names
+    Use {
+        /// The repository in question
         repository: String,
+        /// The version to use, as installed through `dyst install --tag`
+        tag: String,
     },
     /// List all installed repositories
     List,
@@ -57,6 +185,24 @@ enum Commands {
         /// The keyword to search for
         query: String,
     },
+    /// Show everything known about an installed repository
+    Info {
+        /// The repository in question
+        repository: String,
+    },
+    /// Check the package store, database and links for inconsistencies
+    Doctor {
+        /// Repair the problems that can be repaired safely
+        #[arg(long)]
+        fix: bool,
+    },
+    /// List all installed repositories with a newer release
+    Outdated,
+    /// Show when packages were installed, updated, removed, locked, unlocked, renamed or switched
+    History {
+        /// Only show the history of this repository
+        repository: Option<String>,
+    },
     /// Update all downloaded repositories
     Update,
     /// Lock a repository, preventing updates
@@ -86,58 +232,245 @@ enum Commands {
         /// Replace the executable's name (ex. `binary-xyz/binary` to replace `binary-xyz` with `binary`)
         rename: String,
     },
+    /// Print the shell completions for dyst (ex. `dyst completions bash > ~/.local/share/bash-completion/completions/dyst`)
+    Completions {
+        /// The shell to complete for
+        shell: Shell,
+    },
+    /// Print the man page for dyst (ex. `dyst man > ~/.local/share/man/man1/dyst.1`)
+    Man,
+    /// Show or change the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage profiles, each with its own packages and executables
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// List all profiles
+    List,
+    /// Create a profile
+    Create {
+        /// The name of the new profile
+        name: String,
+    },
+    /// Delete a profile with all of its packages
+    Delete {
+        /// The profile to delete
+        name: String,
+        /// Delete the profile even if packages are installed in it
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the directory the active profile links executables into, for adding it to `PATH`
+    Path,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the value of a setting
+    Get {
+        /// The setting in question (ex. `binaries-path`)
+        key: String,
+    },
+    /// Change a setting
+    Set {
+        /// The setting to change
+        key: String,
+        /// The new value
+        value: String,
+        /// Change the project's `.dyst.toml` instead of the global configuration
+        #[arg(long)]
+        project: bool,
+    },
+    /// Remove a setting, restoring its default
+    Unset {
+        /// The setting to remove
+        key: String,
+        /// Change the project's `.dyst.toml` instead of the global configuration
+        #[arg(long)]
+        project: bool,
+    },
+    /// List all settings and the files they are read from
+    List,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let arguments = ArgumentParser::parse();
+    let output = arguments.output;
+
+    if arguments.system {
+        common_directories::use_system_wide_installation();
+    }
+
+    if let Some(profile) = &arguments.profile {
+        common_directories::use_profile(profile.clone());
+    }
+
+    match run(arguments).await {
+        Err(error) if output == OutputFormat::Json => {
+            output::print_error_json(&error);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(arguments: ArgumentParser) -> Result<()> {
+    let output = arguments.output;
+
+    // a broken configuration can still be repaired through `dyst config`
+    if !matches!(
+        arguments.command,
+        Commands::Config { .. } | Commands::Completions { .. } | Commands::Man
+    ) {
+        Config::load()?.initialise_github()?;
+    }
 
     match &arguments.command {
         Commands::Install {
             repository,
             tag,
             prerelease,
+            no_prerelease,
             filter,
             rename,
+            bin,
             lock,
             assets,
+            desktop,
+            strip_components,
+            include,
+            exclude,
+            executable,
+            not_executable,
+            force,
+            skip_conflicts,
+            alias_conflicts,
+            env,
+            arg,
         } => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
             let (author, name) = split_repository_argument(repository)?;
 
-            if is_repository_installed(author, name)? && !*assets {
-                return Err(anyhow!("The requested repository is already installed"));
+            let side_by_side = is_repository_installed(author, name)? && !*assets;
+
+            if side_by_side && tag.is_none() {
+                return Err(anyhow!(
+                    "The requested repository is already installed (consider passing `--tag` to install another version next to it)"
+                ));
+            }
+
+            // additional versions share the settings of the installed one, which `dyst use` links
+            // them with as well, so only what applies to this installation alone can be passed
+            let settings = [
+                (*prerelease || *no_prerelease, "--prerelease"),
+                (!rename.is_empty(), "--rename"),
+                (!bin.is_empty(), "--bin"),
+                (*lock, "--lock"),
+                (*desktop, "--desktop"),
+                (*strip_components != 0, "--strip-components"),
+                (
+                    !include.is_empty() || !exclude.is_empty(),
+                    "--include/--exclude",
+                ),
+                (
+                    !executable.is_empty() || !not_executable.is_empty(),
+                    "--executable/--not-executable",
+                ),
+                (
+                    *skip_conflicts || *alias_conflicts,
+                    "--skip-conflicts/--alias-conflicts",
+                ),
+                (!env.is_empty() || !arg.is_empty(), "--env/--arg"),
+            ]
+            .into_iter()
+            .filter(|(passed, _)| *passed)
+            .map(|(_, flag)| flag)
+            .collect::<Vec<&str>>();
+
+            if side_by_side && !settings.is_empty() {
+                return Err(anyhow!(
+                    "{} cannot be passed when installing another version, which uses the settings of the installed one",
+                    settings.join(", ")
+                ));
             }
 
             let mut installer = cli::install::PackageInstallation::new(&index_db, author, name);
-            installer.prereleases(*prerelease);
+            let config = Config::load()?;
+
+            installer.prereleases(match (prerelease, no_prerelease) {
+                (true, _) => true,
+                (_, true) => false,
+                _ => config.prereleases.unwrap_or(false),
+            });
+            if let Some(libc) = config.libc {
+                installer.preferred_libc(libc);
+            }
             installer.lock(*lock);
+            installer.desktop_entry(*desktop);
+            installer.strip_components(*strip_components);
+            installer.include_paths(ExtractionFilter::parse_patterns(include)?);
+            installer.exclude_paths(ExtractionFilter::parse_patterns(exclude)?);
+            installer.executable_paths(ExtractionFilter::parse_patterns(executable)?);
+            installer.non_executable_paths(ExtractionFilter::parse_patterns(not_executable)?);
+            installer.conflict_policy(match (force, skip_conflicts, alias_conflicts) {
+                (true, _, _) => ConflictPolicy::Force,
+                (_, true, _) => ConflictPolicy::Skip,
+                (_, _, true) => ConflictPolicy::Alias,
+                _ => ConflictPolicy::Abort,
+            });
+
+            // additional versions are installed the way the installed one was
+            if side_by_side {
+                installer.restore_settings()?;
+                installer.side_by_side(true);
+
+                // forcing is never stored, see `ConflictPolicy::persisted`
+                if *force {
+                    installer.conflict_policy(ConflictPolicy::Force);
+                }
+            }
 
-            if tag.is_some() {
-                installer.latest_tag(tag.clone().unwrap());
+            if let Some(tag) = tag {
+                installer.latest_tag(tag.clone());
             }
 
-            if filter.is_some() {
-                let regular_expression = Regex::new(&filter.as_ref().unwrap())
-                    .context("The filter contains illegal regex syntax")?;
+            if let Some(filter) = filter {
+                let regular_expression =
+                    Regex::new(filter).context("The filter contains illegal regex syntax")?;
 
                 installer.asset_regex_filter(regular_expression);
             }
 
-            if rename.is_some() {
-                let search_replace = rename.as_ref().unwrap();
+            for search_replace in rename {
+                let (search, replace) = split_rename_argument(search_replace)?;
 
-                if search_replace.matches('/').count() != 1 {
-                    return Err(anyhow!(
-                        "The provided rename option seems invalid (expected `match/replace`)"
-                    ));
-                }
+                installer.rename_executable(search.to_string(), replace.to_string());
+            }
 
-                let mut search_replace_split_iterator = search_replace.split('/');
-                let search = search_replace_split_iterator.next().unwrap();
-                let replace = search_replace_split_iterator.next().unwrap();
+            for variable in env {
+                let (key, value) = split_env_argument(variable)?;
 
-                installer.rename_executable(search.to_string(), replace.to_string());
+                installer.environment_variable(key.to_string(), value.to_string());
+            }
+
+            for argument in arg {
+                installer.argument(argument.clone());
+            }
+
+            for selection in bin {
+                let (name, alias) = split_bin_argument(selection)?;
+
+                installer.select_executable(name.to_string(), alias.to_string());
             }
 
             installer.fetch_release().await?;
@@ -148,7 +481,7 @@ async fn main() -> Result<()> {
                     installer
                         .selected_release
                         .clone()
-                        .unwrap()
+                        .context("No release was selected prior to installation")?
                         .assets
                         .into_iter()
                         .map(|asset| asset.name)
@@ -159,6 +492,68 @@ async fn main() -> Result<()> {
             }
         }
         Commands::Remove { repository } => {
+            let _store_lock = common_directories::lock_package_store()?;
+            let index_db = common_directories::open_database()?;
+            let (repository, tag) = split_version_argument(repository);
+            let (author, name) = split_repository_argument(repository)?;
+
+            if !is_repository_installed(author, name)? {
+                return Err(anyhow!("The requested repository is not installed"));
+            }
+
+            match tag {
+                Some(tag) => cli::remove::remove_version(&index_db, author, name, tag)?,
+                None => cli::remove::remove_repository(&index_db, author, name).await?,
+            }
+        }
+        Commands::Run {
+            repository,
+            prerelease,
+            filter,
+            bin,
+            keep,
+            arguments,
+        } => {
+            let index_db = common_directories::open_database()?;
+            let (repository, tag) = split_version_argument(repository);
+            let (author, name) = split_repository_argument(repository)?;
+            let mut installer = cli::install::PackageInstallation::new(&index_db, author, name);
+            let config = Config::load()?;
+
+            installer.prereleases(*prerelease || config.prereleases.unwrap_or(false));
+            if let Some(libc) = config.libc {
+                installer.preferred_libc(libc);
+            }
+
+            if let Some(tag) = tag {
+                installer.latest_tag(tag.to_string());
+            }
+
+            if let Some(filter) = filter {
+                let regular_expression =
+                    Regex::new(filter).context("The filter contains illegal regex syntax")?;
+
+                installer.asset_regex_filter(regular_expression);
+            }
+
+            let package_path =
+                cli::run::prepare_release(&index_db, &mut installer, author, name, tag, *keep)
+                    .await?;
+
+            cli::run::run_executable(&installer, &package_path, name, bin.as_deref(), arguments)?;
+        }
+        // no lock is taken, the installation of a missing version takes it itself
+        Commands::Resolve {
+            repository,
+            executable,
+        } => {
+            let index_db = common_directories::open_database()?;
+            let (author, name) = split_repository_argument(repository)?;
+
+            cli::resolve::resolve_version(&index_db, author, name, executable.as_deref())?;
+        }
+        Commands::Use { repository, tag } => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
             let (author, name) = split_repository_argument(repository)?;
 
@@ -166,20 +561,55 @@ async fn main() -> Result<()> {
                 return Err(anyhow!("The requested repository is not installed"));
             }
 
-            cli::remove::uninstall_package(&index_db, author, name).await?;
+            cli::use_version::use_version(&index_db, author, name, tag).await?;
         }
         Commands::List => {
-            cli::list::list_repositories().await?;
+            cli::list::list_repositories(output).await?;
         }
         Commands::Search { query } => {
-            cli::search::search_repositories(query).await?;
+            cli::search::search_repositories(query, output).await?;
+        }
+        Commands::Info { repository } => {
+            let index_db = common_directories::open_database()?;
+            let (author, name) = split_repository_argument(repository)?;
+
+            if !is_repository_installed(author, name)? {
+                return Err(anyhow!("The requested repository is not installed"));
+            }
+
+            cli::info::show_package_info(&index_db, author, name, output).await?;
+        }
+        Commands::Doctor { fix } => {
+            let _store_lock = match *fix {
+                true => Some(common_directories::lock_package_store()?),
+                false => None,
+            };
+            let index_db = common_directories::open_database()?;
+
+            cli::doctor::check_installation(&index_db, *fix).await?;
+        }
+        Commands::Outdated => {
+            let index_db = common_directories::open_database()?;
+
+            cli::outdated::list_outdated(&index_db, output).await?;
+        }
+        Commands::History { repository } => {
+            let index_db = common_directories::open_database()?;
+
+            if let Some(repository) = repository {
+                split_repository_argument(repository)?;
+            }
+
+            cli::history::show_history(&index_db, repository.as_deref(), output)?;
         }
         Commands::Update => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
 
             cli::update::update_repositories(&index_db).await?;
         }
         Commands::Lock { repository } => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
             let (author, name) = split_repository_argument(repository)?;
 
@@ -190,6 +620,7 @@ async fn main() -> Result<()> {
             cli::lock::lock_package(&index_db, author, name).await?;
         }
         Commands::Unlock { repository } => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
             let (author, name) = split_repository_argument(repository)?;
 
@@ -200,6 +631,7 @@ async fn main() -> Result<()> {
             cli::lock::unlock_package(&index_db, author, name).await?;
         }
         Commands::AllowPrereleases { repository } => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
             let (author, name) = split_repository_argument(repository)?;
 
@@ -216,9 +648,10 @@ async fn main() -> Result<()> {
                 return Err(anyhow!("The requested repository is not installed"));
             }
 
-            cli::list::list_executables(author, name)?;
+            cli::list::list_executables(author, name, output)?;
         }
         Commands::Rename { repository, rename } => {
+            let _store_lock = common_directories::lock_package_store()?;
             let index_db = common_directories::open_database()?;
             let (author, name) = split_repository_argument(repository)?;
 
@@ -226,18 +659,33 @@ async fn main() -> Result<()> {
                 return Err(anyhow!("The requested repository is not installed"));
             }
 
-            if rename.matches('/').count() != 1 {
-                return Err(anyhow!(
-                    "The provided rename option seems invalid (expected `match/replace`)"
-                ));
-            }
-
-            let mut search_replace_split_iterator = rename.split('/');
-            let search = search_replace_split_iterator.next().unwrap();
-            let replace = search_replace_split_iterator.next().unwrap();
+            let (search, replace) = split_rename_argument(rename)?;
 
-            cli::rename::rename_executable(&index_db, author, name, search, replace).await?;
+            cli::rename::rename_executable(&index_db, author, name, search, replace, output)
+                .await?;
+        }
+        Commands::Completions { shell } => {
+            cli::completions::print_completions(*shell, &mut ArgumentParser::command())?;
         }
+        Commands::Man => {
+            cli::completions::print_man_page(ArgumentParser::command())?;
+        }
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key } => cli::config::get_setting(key)?,
+            ConfigAction::Set {
+                key,
+                value,
+                project,
+            } => cli::config::set_setting(key, Some(value), *project)?,
+            ConfigAction::Unset { key, project } => cli::config::set_setting(key, None, *project)?,
+            ConfigAction::List => cli::config::list_settings(output)?,
+        },
+        Commands::Profile { action } => match action {
+            ProfileAction::List => cli::profile::list_profiles(output)?,
+            ProfileAction::Create { name } => cli::profile::create_profile(name)?,
+            ProfileAction::Delete { name, force } => cli::profile::delete_profile(name, *force)?,
+            ProfileAction::Path => cli::profile::print_binaries_path()?,
+        },
     }
 
     Ok(())
@@ -250,11 +698,59 @@ fn split_repository_argument(repository: &str) -> Result<(&str, &str)> {
         ));
     }
 
-    let mut split_iterator = repository.split('/');
-    let author = split_iterator.next().unwrap();
-    let name = split_iterator.next().unwrap();
+    repository
+        .split_once('/')
+        .context("The provided repository seems invalid (expected `author/name`)")
+}
+
+// `author/name@tag` selects a version of a repository
+fn split_version_argument(repository: &str) -> (&str, Option<&str>) {
+    match repository.split_once('@') {
+        Some((repository, tag)) => (repository, Some(tag)),
+        None => (repository, None),
+    }
+}
+
+fn split_rename_argument(rename: &str) -> Result<(&str, &str)> {
+    if rename.matches('/').count() != 1 {
+        return Err(anyhow!(
+            "The provided rename option seems invalid (expected `match/replace`)"
+        ));
+    }
+
+    rename
+        .split_once('/')
+        .context("The provided rename option seems invalid (expected `match/replace`)")
+}
+
+fn split_bin_argument(selection: &str) -> Result<(&str, &str)> {
+    let (name, alias) = selection.split_once('=').unwrap_or((selection, selection));
+
+    if name.is_empty() || alias.is_empty() || alias.contains('/') {
+        return Err(anyhow!(
+            "The provided bin option seems invalid (expected `name` or `name=alias`)"
+        ));
+    }
+
+    Ok((name, alias))
+}
+
+fn split_env_argument(variable: &str) -> Result<(&str, &str)> {
+    let (key, value) = variable.split_once('=').unwrap_or_default();
+
+    if key.is_empty()
+        || key.starts_with(|character: char| character.is_ascii_digit())
+        || !key
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || character == '_')
+        || value.contains('\n')
+    {
+        return Err(anyhow!(
+            "The provided env option seems invalid (expected `KEY=VALUE`)"
+        ));
+    }
 
-    Ok((author, name))
+    Ok((key, value))
 }
 
 fn is_repository_installed(author: &str, name: &str) -> Result<bool> {
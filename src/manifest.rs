@@ -0,0 +1,94 @@
+use crate::links;
+use anyhow::Result;
+use std::fs::{remove_dir_all, remove_file};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// every file extracted into a package directory is recorded next to the package's links, so
+// that nothing has to be guessed from the file system when the package is removed or checked
+pub fn record_package_files(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    package_path: &Path,
+) -> Result<()> {
+    for entry in WalkDir::new(package_path) {
+        let entry = entry?;
+
+        if entry.file_type().is_dir() {
+            continue;
+        }
+
+        let mut statement = index_db.prepare(
+            "INSERT OR REPLACE INTO files (path, repository, kind) VALUES(?, ?, 'file')",
+        )?;
+        statement.bind(1, links::path_to_str(entry.path())?)?;
+        statement.bind(2, repository)?;
+        while statement.next()? != sqlite3::State::Done {}
+    }
+
+    Ok(())
+}
+
+pub fn read_package_files(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+) -> Result<Vec<PathBuf>> {
+    let mut package_files = Vec::new();
+    let mut statement = index_db
+        .prepare("SELECT path FROM files WHERE repository = ? AND kind = 'file' ORDER BY path")?;
+    statement.bind(1, repository)?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        package_files.push(PathBuf::from(statement.read::<String>(0)?));
+    }
+
+    Ok(package_files)
+}
+
+// packages installed before files were recorded have no rows at all
+pub fn is_recorded(index_db: &sqlite3::Connection, repository: &str) -> Result<bool> {
+    let mut statement = index_db.prepare("SELECT 1 FROM files WHERE repository = ? LIMIT 1")?;
+    statement.bind(1, repository)?;
+
+    Ok(statement.next()? == sqlite3::State::Row)
+}
+
+// whether a directory holds anything dyst has extracted, e.g. one left behind by a failed removal
+pub fn is_recorded_below(index_db: &sqlite3::Connection, directory: &Path) -> Result<bool> {
+    let mut statement = index_db.prepare(
+        "SELECT 1 FROM files WHERE substr(path, 1, length(?1)) = ?1 OR substr(target, 1, length(?1)) = ?1 LIMIT 1",
+    )?;
+    statement.bind(1, format!("{}/", links::path_to_str(directory)?).as_str())?;
+
+    Ok(statement.next()? == sqlite3::State::Row)
+}
+
+pub fn forget_package_files(index_db: &sqlite3::Connection, repository: &str) -> Result<()> {
+    let mut statement =
+        index_db.prepare("DELETE FROM files WHERE repository = ? AND kind = 'file'")?;
+    statement.bind(1, repository)?;
+    while statement.next()? != sqlite3::State::Done {}
+
+    Ok(())
+}
+
+pub fn remove_package_files(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    package_path: &Path,
+) -> Result<()> {
+    for file_path in read_package_files(index_db, repository)? {
+        if file_path.symlink_metadata().is_ok() {
+            remove_file(&file_path)?;
+        }
+    }
+
+    forget_package_files(index_db, repository)?;
+
+    // the directories and anything the package has written at runtime
+    if package_path.exists() {
+        remove_dir_all(package_path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,287 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs::copy;
+use std::path::Path;
+
+// the schema every database starts out with, as created by the first releases of dyst
+const INITIAL_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS packages (
+        repository TEXT PRIMARY KEY UNIQUE,
+        tag TEXT NOT NULL,
+        lock INTEGER NOT NULL,
+        assetFilter TEXT,
+        execRename TEXT,
+        preReleases INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS dyst (
+        key TEXT PRIMARY KEY UNIQUE,
+        value TEXT
+    );
+    INSERT OR IGNORE INTO dyst (key, value) VALUES('version', '1');
+";
+
+// migration `n` upgrades the schema from version `n + 1` to `n + 2`; never edit one that has
+// been released, append a new one instead
+const MIGRATIONS: &[&str] = &[
+    // 2: extraction filters, see `--strip-components`, `--include` and `--exclude`
+    "
+    ALTER TABLE packages ADD COLUMN stripComponents INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE packages ADD COLUMN includeGlobs TEXT;
+    ALTER TABLE packages ADD COLUMN excludeGlobs TEXT;
+    ",
+    // 3: the globs executables are detected by
+    "
+    ALTER TABLE packages ADD COLUMN executableGlobs TEXT;
+    ALTER TABLE packages ADD COLUMN nonExecutableGlobs TEXT;
+    ",
+    // 4: the link names chosen per executable
+    "
+    ALTER TABLE packages ADD COLUMN exclusiveLinks INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE executableLinks (
+        repository TEXT NOT NULL,
+        executable TEXT NOT NULL,
+        linkName TEXT NOT NULL
+    );
+    ",
+    // 5: how link conflicts are resolved and which package owns a link
+    "
+    ALTER TABLE packages ADD COLUMN linkConflicts TEXT NOT NULL DEFAULT 'abort';
+    CREATE TABLE links (
+        path TEXT PRIMARY KEY UNIQUE,
+        repository TEXT NOT NULL,
+        target TEXT NOT NULL
+    );
+    ",
+    // 6: the environment variables and arguments of launcher scripts
+    "
+    ALTER TABLE packages ADD COLUMN shimEnvironment TEXT;
+    ALTER TABLE packages ADD COLUMN shimArguments TEXT;
+    ",
+    // 7: man pages and shell completions are linked next to executables
+    "
+    ALTER TABLE links ADD COLUMN kind TEXT NOT NULL DEFAULT 'executable';
+    ",
+    // 8: the details shown by `dyst info`
+    "
+    ALTER TABLE packages ADD COLUMN installedAt TEXT;
+    ALTER TABLE packages ADD COLUMN assetName TEXT;
+    ALTER TABLE packages ADD COLUMN assetSize INTEGER;
+    ALTER TABLE packages ADD COLUMN assetUrl TEXT;
+    ",
+    // 9: links move into a manifest of everything a package installed, extracted files have no
+    // target
+    "
+    CREATE TABLE files (
+        path TEXT PRIMARY KEY UNIQUE,
+        repository TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        target TEXT
+    );
+    INSERT INTO files (path, repository, kind, target) SELECT path, repository, kind, target FROM links;
+    DROP TABLE links;
+    ",
+    // 10: the checksum of installed assets and the append-only history shown by `dyst history`
+    "
+    ALTER TABLE packages ADD COLUMN assetChecksum TEXT;
+    CREATE TABLE history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp TEXT NOT NULL,
+        repository TEXT NOT NULL,
+        action TEXT NOT NULL,
+        oldTag TEXT,
+        newTag TEXT,
+        assetName TEXT,
+        checksum TEXT,
+        details TEXT,
+        user TEXT,
+        commandLine TEXT NOT NULL
+    );
+    CREATE TRIGGER historyNoUpdate BEFORE UPDATE ON history
+    BEGIN
+        SELECT RAISE(ABORT, 'the history is append-only');
+    END;
+    CREATE TRIGGER historyNoDelete BEFORE DELETE ON history
+    BEGIN
+        SELECT RAISE(ABORT, 'the history is append-only');
+    END;
+    ",
+    // 11: versions installed next to the one in `packages`, see `dyst use`
+    "
+    CREATE TABLE versions (
+        repository TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        installedAt TEXT,
+        assetName TEXT,
+        assetSize INTEGER,
+        assetUrl TEXT,
+        assetChecksum TEXT,
+        PRIMARY KEY (repository, tag)
+    );
+    ",
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64 + 1
+}
+
+// other dyst processes may open the database at the same time, so the version is only read once
+// it is locked for writing, and all migrations are applied in that one transaction
+pub fn migrate(
+    connection: &sqlite3::Connection,
+    database_path: &Path,
+    created: bool,
+) -> Result<()> {
+    // an up-to-date database is only read, as it need not be writable (ex. the system-wide store
+    // opened by another user)
+    if !created && is_current(connection)? {
+        return Ok(());
+    }
+
+    connection.execute("BEGIN IMMEDIATE;")?;
+
+    match migrate_locked(connection, database_path, created) {
+        Ok(()) => Ok(connection.execute("COMMIT;")?),
+        Err(error) => {
+            let _ = connection.execute("ROLLBACK;"); // ignore error, the transaction may have ended
+
+            Err(error)
+        }
+    }
+}
+
+fn migrate_locked(
+    connection: &sqlite3::Connection,
+    database_path: &Path,
+    created: bool,
+) -> Result<()> {
+    connection.execute(INITIAL_SCHEMA)?;
+
+    let version = read_version(connection)?;
+
+    if version < 1 {
+        return Err(anyhow!(
+            "The index database specifies an invalid schema version"
+        ));
+    }
+
+    if version > latest_version() {
+        return Err(anyhow!(
+            "The index database uses schema version {}, but this version of dyst only supports up to {} (consider updating dyst)",
+            version,
+            latest_version()
+        ));
+    }
+
+    if version == latest_version() {
+        return Ok(());
+    }
+
+    // a fresh database has nothing worth backing up; nothing has been written to the file yet
+    if !created {
+        let backup_path = database_path.with_extension(format!("db3.v{}.bak", version));
+
+        copy(database_path, &backup_path).context(format!(
+            "The index database could not be backed up to {} before migrating it",
+            backup_path.display()
+        ))?;
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        let target_version = index as i64 + 2;
+
+        connection
+            .execute(format!(
+                "{} UPDATE dyst SET value = '{}' WHERE key = 'version';",
+                migration, target_version
+            ))
+            .map_err(|error| {
+                anyhow!(
+                    "The index database could not be migrated to schema version {} ({})",
+                    target_version,
+                    error
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+fn is_current(connection: &sqlite3::Connection) -> Result<bool> {
+    let mut statement = connection
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'dyst'")?;
+
+    if statement.next()? != sqlite3::State::Row {
+        return Ok(false);
+    }
+
+    Ok(read_version(connection)? == latest_version())
+}
+
+fn read_version(connection: &sqlite3::Connection) -> Result<i64> {
+    let mut statement = connection.prepare("SELECT value FROM dyst WHERE key = 'version'")?;
+
+    if statement.next()? != sqlite3::State::Row {
+        return Err(anyhow!(
+            "The index database does not specify a schema version"
+        ));
+    }
+
+    statement
+        .read::<String>(0)?
+        .parse()
+        .context("The index database specifies an invalid schema version")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn databases_of_the_first_releases_are_migrated() {
+        let directory = tempfile::tempdir().unwrap();
+        let database_path = directory.path().join("index.db3");
+
+        let connection = sqlite3::open(&database_path).unwrap();
+        connection.execute(INITIAL_SCHEMA).unwrap();
+        connection
+            .execute("INSERT INTO packages (repository, tag, lock, preReleases) VALUES('author/name', 'v1', 0, 0)")
+            .unwrap();
+
+        migrate(&connection, &database_path, false).unwrap();
+        assert_eq!(read_version(&connection).unwrap(), latest_version());
+        assert!(directory.path().join("index.db3.v1.bak").is_file());
+
+        let mut statement = connection
+            .prepare("SELECT stripComponents, linkConflicts FROM packages WHERE repository = 'author/name'")
+            .unwrap();
+        assert_eq!(statement.next().unwrap(), sqlite3::State::Row);
+        assert_eq!(statement.read::<i64>(0).unwrap(), 0);
+        assert_eq!(statement.read::<String>(1).unwrap(), "abort");
+        drop(statement);
+
+        // another process finding the database migrated already leaves it alone
+        let other_connection = sqlite3::open(&database_path).unwrap();
+        migrate(&other_connection, &database_path, false).unwrap();
+        assert_eq!(read_version(&other_connection).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn up_to_date_databases_are_opened_read_only() {
+        let directory = tempfile::tempdir().unwrap();
+        let database_path = directory.path().join("index.db3");
+        migrate(
+            &sqlite3::open(&database_path).unwrap(),
+            &database_path,
+            true,
+        )
+        .unwrap();
+
+        // writing fails with "attempt to write a readonly database", like in a store owned by root
+        let connection = sqlite3::open(&database_path).unwrap();
+        connection.execute("PRAGMA query_only = ON;").unwrap();
+        assert!(connection
+            .execute("INSERT OR IGNORE INTO dyst (key, value) VALUES('version', '1');")
+            .is_err());
+
+        migrate(&connection, &database_path, false).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    // the historical, space-separated output
+    #[default]
+    Plain,
+    Table,
+    Json,
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+
+    Ok(())
+}
+
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let widths = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .fold(header.len(), usize::max)
+        })
+        .collect::<Vec<usize>>();
+
+    let header_row = headers
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<String>>();
+
+    for row in [&header_row].into_iter().chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorOutput {
+    error: String,
+    // the chain of underlying errors, outermost first
+    causes: Vec<String>,
+}
+
+// scripts consuming JSON get a parsable error on stderr instead of anyhow's report
+pub fn print_error_json(error: &anyhow::Error) {
+    let output = ErrorOutput {
+        error: error.to_string(),
+        causes: error.chain().skip(1).map(ToString::to_string).collect(),
+    };
+
+    eprintln!(
+        "{}",
+        serde_json::to_string_pretty(&output).unwrap_or_else(|_| error.to_string())
+    );
+}
@@ -0,0 +1,163 @@
+use crate::common_directories;
+use crate::extraction_filter::ExtractionFilter;
+use anyhow::Result;
+use regex::Regex;
+use std::fs::metadata;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// finds man pages and shell completions by their conventional names and pairs them with the
+// path they are linked to
+pub fn find_shared_files(
+    package_path: &Path,
+    extraction_filter: &ExtractionFilter,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let data_path = common_directories::get_shared_data_path()?;
+    // ex. `rg.1`, `tool-subcommand.1.gz` or `tool.8`, but not `node-v1.2.3` or `tool-1.2`
+    let man_page_regex = Regex::new(r"^[A-Za-z](?:[\w.+-]*[A-Za-z_+-])?\.([1-9])[a-z]*(\.gz)?$")?;
+    let mut shared_files = Vec::new();
+
+    for entry in WalkDir::new(package_path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let relative_path = entry.path().strip_prefix(package_path)?;
+
+        if !extraction_filter.is_selected(relative_path) {
+            continue;
+        }
+
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let in_bash_completions_directory = relative_path.parent().is_some_and(|parent| {
+            let parent = parent.to_string_lossy();
+            parent.contains("bash-completion") || parent.contains("bash_completion")
+        });
+        // scripts and functions named like completions are only linked from where completions are
+        // kept, so that helpers (ex. `lib/util.bash` or `conf.d/env.fish`) never shadow real ones
+        let in_completions_directory = relative_path.parent().is_some_and(|parent| {
+            parent
+                .components()
+                .any(|component| component.as_os_str().to_string_lossy().contains("complet"))
+        });
+
+        let link_path = if let Some(captures) = man_page_regex.captures(&file_name) {
+            // versioned shared libraries (`libtool.so.1`) and executables look alike
+            if file_name.contains(".so.")
+                || metadata(entry.path())?.permissions().mode() & 0o111 != 0
+            {
+                continue;
+            }
+
+            data_path
+                .join("man")
+                .join(format!("man{}", &captures[1]))
+                .join(&file_name)
+        } else if let Some(command) = file_name.strip_suffix(".bash-completion").or_else(|| {
+            file_name
+                .strip_suffix(".bash")
+                .filter(|_| in_completions_directory)
+        }) {
+            // bash-completion looks completions up by the command's name
+            data_path
+                .join("bash-completion")
+                .join("completions")
+                .join(command)
+        } else if in_bash_completions_directory && !file_name.contains('.') {
+            data_path
+                .join("bash-completion")
+                .join("completions")
+                .join(&file_name)
+        } else if file_name.starts_with('_') && !file_name.contains('.') && in_completions_directory
+        {
+            data_path
+                .join("zsh")
+                .join("site-functions")
+                .join(&file_name)
+        } else if let Some(command) = file_name
+            .strip_suffix(".zsh")
+            .filter(|_| in_completions_directory)
+        {
+            data_path
+                .join("zsh")
+                .join("site-functions")
+                .join(format!("_{}", command))
+        } else if file_name.ends_with(".fish") && in_completions_directory {
+            data_path
+                .join("fish")
+                .join("vendor_completions.d")
+                .join(&file_name)
+        } else {
+            continue;
+        };
+
+        shared_files.push((entry.path().to_path_buf(), link_path));
+    }
+
+    Ok(shared_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    fn find_linked_names(file_paths: &[&str]) -> Vec<String> {
+        let package_directory = tempfile::tempdir().unwrap();
+
+        for file_path in file_paths {
+            let path = package_directory.path().join(file_path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, "").unwrap();
+        }
+
+        let mut linked_names =
+            find_shared_files(package_directory.path(), &ExtractionFilter::default())
+                .unwrap()
+                .into_iter()
+                .map(|(target, _)| {
+                    target
+                        .strip_prefix(package_directory.path())
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect::<Vec<String>>();
+        linked_names.sort();
+
+        linked_names
+    }
+
+    #[test]
+    fn man_pages_are_told_apart_from_versioned_names() {
+        assert_eq!(
+            find_linked_names(&["doc/rg.1", "tool.8.gz", "node-v1.2.3", "foo-1.2", "x.1"]),
+            ["doc/rg.1", "tool.8.gz", "x.1"]
+        );
+    }
+
+    #[test]
+    fn completions_are_only_linked_from_completion_directories() {
+        assert_eq!(
+            find_linked_names(&[
+                "completions/tool.bash",
+                "completions/tool.fish",
+                "completions/_tool",
+                "complete/tool.zsh",
+                "tool.bash-completion",
+                "lib/util.bash",
+                "conf.d/env.fish",
+                "_internal",
+                "lib/completer.bash",
+            ]),
+            [
+                "complete/tool.zsh",
+                "completions/_tool",
+                "completions/tool.bash",
+                "completions/tool.fish",
+                "tool.bash-completion",
+            ]
+        );
+    }
+}
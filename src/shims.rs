@@ -0,0 +1,180 @@
+use crate::version_files::VERSION_FILE_NAME;
+use crate::{common_directories, versions};
+use anyhow::Result;
+use std::fs::{set_permissions, File, Permissions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+const SHIM_MARKER: &str = "# generated by dyst";
+
+#[derive(Clone, Default)]
+pub struct Shim {
+    pub environment: Vec<(String, String)>,
+    pub arguments: Vec<String>,
+}
+
+impl Shim {
+    pub fn from_index_db(
+        index_db: &sqlite3::Connection,
+        repository_author: &str,
+        repository_name: &str,
+    ) -> Result<Shim> {
+        let mut statement = index_db
+            .prepare("SELECT shimEnvironment, shimArguments FROM packages WHERE repository = ?")?;
+        statement.bind(
+            1,
+            format!("{}/{}", repository_author, repository_name).as_str(),
+        )?;
+
+        if statement.next()? != sqlite3::State::Row {
+            return Ok(Shim::default());
+        }
+
+        Ok(Shim {
+            environment: Self::deserialize_lines(statement.read::<String>(0).ok())
+                .into_iter()
+                .filter_map(|variable| {
+                    variable
+                        .split_once('=')
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                })
+                .collect(),
+            arguments: Self::deserialize_lines(statement.read::<String>(1).ok()),
+        })
+    }
+
+    // without environment variables or arguments, plain symlinks do the job
+    pub fn is_enabled(&self) -> bool {
+        !self.environment.is_empty() || !self.arguments.is_empty()
+    }
+
+    // both are stored newline-separated in the index database, like glob patterns
+    pub fn serialize_environment(&self) -> Option<String> {
+        Self::serialize_lines(
+            self.environment
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect(),
+        )
+    }
+
+    pub fn serialize_arguments(&self) -> Option<String> {
+        Self::serialize_lines(self.arguments.clone())
+    }
+
+    // with `version_files`, the target is looked up in the version pinned for the current directory
+    pub fn write(
+        &self,
+        repository: &str,
+        target: &Path,
+        shim_path: &Path,
+        version_files: bool,
+    ) -> Result<()> {
+        let package_path = versions::get_owner_path(repository)?;
+
+        let mut script = format!(
+            "#!/bin/sh\n{} for {}, changes are overwritten\nDYST_PACKAGE={}\n",
+            SHIM_MARKER,
+            repository,
+            Self::quote(&package_path.to_string_lossy())
+        );
+
+        let relative_target = target
+            .strip_prefix(&package_path)
+            .ok()
+            .filter(|_| version_files);
+
+        // dyst is only asked if a version file pins the repository, grep is way faster to start; it
+        // prints the directory of the pinned version and the executable within it on separate lines
+        if let Some(relative_target) = relative_target {
+            script.push_str(&format!(
+                "executable={}\ndirectory=${{PWD:-/}}\nwhile :; do\n    if grep -Eqs {} \"$directory/{}\"; then\n        resolved=$({} resolve -- {} {}) || exit 1\n        DYST_PACKAGE=${{resolved%\n*}}\n        executable=${{resolved##*\n}}\n        break\n    fi\n    [ \"$directory\" = / ] && break\n    directory=$(dirname \"$directory\")\ndone\n",
+                Self::quote(&target.to_string_lossy()),
+                Self::quote(&format!(
+                    "^[[:space:]]*{}[[:space:]]",
+                    repository.replace('.', "\\.")
+                )),
+                VERSION_FILE_NAME,
+                Self::dyst_command(),
+                Self::quote(repository),
+                Self::quote(&relative_target.to_string_lossy())
+            ));
+        }
+
+        // values are double-quoted, so that they can refer to `$DYST_PACKAGE` or other variables
+        for (key, value) in &self.environment {
+            script.push_str(&format!(
+                "export {}=\"{}\"\n",
+                key,
+                value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('`', "\\`")
+            ));
+        }
+
+        match relative_target {
+            Some(_) => script.push_str("exec \"$executable\""),
+            None => script.push_str(&format!("exec {}", Self::quote(&target.to_string_lossy()))),
+        }
+        for argument in &self.arguments {
+            script.push(' ');
+            script.push_str(&Self::quote(argument));
+        }
+        script.push_str(" \"$@\"\n");
+
+        File::create(shim_path)?.write_all(script.as_bytes())?;
+        set_permissions(shim_path, Permissions::from_mode(0o755))?;
+
+        Ok(())
+    }
+
+    // whether a file in the executables path was written by `write`
+    pub fn is_shim(path: &Path) -> bool {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return false,
+        };
+
+        BufReader::new(file)
+            .lines()
+            .nth(1)
+            .and_then(Result::ok)
+            .is_some_and(|line| line.starts_with(SHIM_MARKER))
+    }
+
+    // the pinned version is resolved in the installation and profile the script belongs to
+    fn dyst_command() -> String {
+        let mut command = String::from("dyst");
+
+        if common_directories::is_system_wide() {
+            command.push_str(" --system");
+        }
+
+        if let Some(profile) = common_directories::get_profile() {
+            command.push_str(&format!(" --profile {}", Self::quote(&profile)));
+        }
+
+        command
+    }
+
+    fn quote(value: &str) -> String {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+
+    fn serialize_lines(lines: Vec<String>) -> Option<String> {
+        if lines.is_empty() {
+            return None;
+        }
+
+        Some(lines.join("\n"))
+    }
+
+    fn deserialize_lines(lines: Option<String>) -> Vec<String> {
+        match lines {
+            Some(lines) => lines.lines().map(str::to_string).collect(),
+            None => Vec::new(),
+        }
+    }
+}
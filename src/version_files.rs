@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+// looked for in the current directory and its parents, each line pins `author/name tag`
+pub const VERSION_FILE_NAME: &str = ".dyst-versions";
+
+// the nearest file pinning the repository wins, so that a project only needs to pin what it
+// cares about
+pub fn find_pinned_version(repository: &str) -> Result<Option<(String, PathBuf)>> {
+    for directory in env::current_dir()?.ancestors() {
+        let version_file = directory.join(VERSION_FILE_NAME);
+
+        if !version_file.is_file() {
+            continue;
+        }
+
+        if let Some((_, tag)) = read_version_file(&version_file)?
+            .into_iter()
+            .find(|(pinned_repository, _)| pinned_repository == repository)
+        {
+            return Ok(Some((tag, version_file)));
+        }
+    }
+
+    Ok(None)
+}
+
+// empty lines and comments starting with `#` are ignored
+pub fn read_version_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let mut pinned_versions = Vec::new();
+
+    for (index, line) in read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // the tag ends up in paths below the package store, so it must not lead out of it
+        match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            [repository, tag] if repository.matches('/').count() == 1 && is_valid_tag(tag) => {
+                pinned_versions.push((repository.to_string(), tag.to_string()))
+            }
+            _ => {
+                return Err(anyhow!(
+                    "The version file {} is invalid in line {} (expected `author/name tag`, without `..`, `/` or `\\` in the tag)",
+                    path.display(),
+                    index + 1
+                ))
+            }
+        }
+    }
+
+    Ok(pinned_versions)
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.contains("..") && !tag.contains(['/', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    #[test]
+    fn tags_leading_out_of_the_package_store_are_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let version_file = directory.path().join(VERSION_FILE_NAME);
+
+        write(&version_file, "# pinned\nhashicorp/terraform v1.5.7\n").unwrap();
+        assert_eq!(
+            read_version_file(&version_file).unwrap(),
+            vec![(String::from("hashicorp/terraform"), String::from("v1.5.7"))]
+        );
+
+        for tag in ["../../bin", "v1/../x", "..", "a\\b"] {
+            write(&version_file, format!("hashicorp/terraform {}\n", tag)).unwrap();
+            assert!(read_version_file(&version_file).is_err(), "{}", tag);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::common_directories;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+// versions installed next to the default one are kept apart from the `author/name` directories,
+// as GitHub user names cannot start with a dot
+pub fn get_version_path(
+    repository_author: &str,
+    repository_name: &str,
+    tag: &str,
+) -> Result<PathBuf> {
+    let mut version_path = common_directories::get_package_store()?;
+    version_path.push(".versions");
+    version_path.push(repository_author);
+    version_path.push(repository_name);
+    version_path.push(tag.replace('/', "_"));
+
+    Ok(version_path)
+}
+
+// the directory of the package or version owning links and files under `owner`
+pub fn get_owner_path(owner: &str) -> Result<PathBuf> {
+    match owner.split_once('@') {
+        Some((repository, tag)) => {
+            let (repository_author, repository_name) = repository
+                .split_once('/')
+                .context("The repository of a version seems invalid")?;
+
+            get_version_path(repository_author, repository_name, tag)
+        }
+        None => Ok(common_directories::get_package_store()?.join(owner)),
+    }
+}
+
+// additional versions own their links and files under this name
+pub fn get_version_key(repository: &str, tag: &str) -> String {
+    format!("{}@{}", repository, tag)
+}
+
+// `terraform` of `v1.5.7` is linked as `terraform@1.5.7`
+pub fn get_link_suffix(tag: &str) -> String {
+    format!(
+        "@{}",
+        tag.strip_prefix('v').unwrap_or(tag).replace('/', "_")
+    )
+}
+
+pub fn list_versions(index_db: &sqlite3::Connection, repository: &str) -> Result<Vec<String>> {
+    let mut tags = Vec::new();
+    let mut statement =
+        index_db.prepare("SELECT tag FROM versions WHERE repository = ? ORDER BY tag")?;
+    statement.bind(1, repository)?;
+
+    while let sqlite3::State::Row = statement.next()? {
+        tags.push(statement.read::<String>(0)?);
+    }
+
+    Ok(tags)
+}
+
+pub fn is_version_installed(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    tag: &str,
+) -> Result<bool> {
+    Ok(list_versions(index_db, repository)?
+        .iter()
+        .any(|version| version == tag))
+}
+
+// whether the tag is installed as the default or as an additional version
+pub fn is_tag_installed(
+    index_db: &sqlite3::Connection,
+    repository: &str,
+    tag: &str,
+) -> Result<bool> {
+    let mut statement = index_db.prepare("SELECT 1 FROM packages WHERE repository = ?1 AND tag = ?2 UNION SELECT 1 FROM versions WHERE repository = ?1 AND tag = ?2")?;
+    statement.bind(1, repository)?;
+    statement.bind(2, tag)?;
+
+    Ok(statement.next()? == sqlite3::State::Row)
+}